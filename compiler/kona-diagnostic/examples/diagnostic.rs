@@ -5,7 +5,7 @@
 
 use std::{path::PathBuf, rc::Rc};
 
-use kona_diagnostic::{source::SourceMap, diagnostic::DiagnosticEngine};
+use kona_diagnostic::{source::SourceMap, diagnostic::{DiagnosticEngine, Applicability}};
 
 fn main() {
     let file = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
@@ -13,11 +13,15 @@ fn main() {
     let source_map = Rc::new(SourceMap::new());
     source_map
         .load_local_file(file.clone())
-        .expect(&format!("failed to read {}", file.to_string_lossy()));
+        .unwrap_or_else(|_| panic!("failed to read {}", file.to_string_lossy()));
 
     let engine = DiagnosticEngine::with_tty_emitter(source_map);
 
     engine.create_err("unexpected token keyword `in` in case-of expression")
         .set_primary_label(141..143u32, "expect keyword `of`")
+        .add_sublabel(134..138u32, "case-of expression starts here")
+        .add_note("the syntax of case-of expression is `case exp of match`")
+        .add_suggestion(141..143u32, "replace `in` with `of`", "of",
+            Applicability::MachineApplicable)
         .emit();
 }
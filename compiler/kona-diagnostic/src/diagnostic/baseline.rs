@@ -0,0 +1,292 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{collections::BTreeMap, fs, io, path::{Path, PathBuf}};
+
+use serde::{Serialize, Deserialize};
+
+use crate::source::SourceMap;
+
+use super::{Diagnostic, Level};
+
+/// A stable identity of a diagnostic, recorded in a [`Baseline`].
+///
+/// It doesn't contain any position, so it stays the same when the files are
+/// loaded in another order or the code around the diagnostic changes.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// The error code, or the lint name for lints without a code.
+    pub code: Option<String>,
+
+    /// The path of the file of the primary span, relative to the root of the
+    /// baseline if it's inside.
+    pub file: Option<String>,
+
+    /// The source code of the primary span, with all whitespace sequences
+    /// replaced by a single space.
+    pub snippet: Option<String>,
+
+    pub message: String,
+}
+
+impl Fingerprint {
+    /// Creates the fingerprint of a diagnostic. Local files are named by their
+    /// path relative to `root`, so that it doesn't depend on the working
+    /// directory or the path the file was loaded with.
+    pub fn new(
+        diag: &Diagnostic, source_map: Option<&SourceMap>, root: Option<&Path>,
+    ) -> Fingerprint {
+        let span = diag.span();
+        let resolved = source_map
+            .filter(|_| !span.is_dummy())
+            .and_then(|source_map| {
+                let file = source_map.lookup_file_at_span(span).ok()?;
+                let source = source_map.lookup_source(span).ok()?;
+                let name = match (file.local_path(), root) {
+                    (Some(path), Some(root)) => {
+                        let path = path.strip_prefix(root).unwrap_or(path);
+                        path.to_string_lossy().replace('\\', "/")
+                    }
+                    _ => file.name(),
+                };
+                Some((name, source.split_whitespace().collect::<Vec<_>>().join(" ")))
+            });
+        let (file, snippet) = resolved.unzip();
+
+        Fingerprint {
+            code: diag.code.clone().or_else(|| diag.lint.map(|lint| lint.name.to_string())),
+            file,
+            snippet,
+            message: diag.message.clone(),
+        }
+    }
+}
+
+/// Known diagnostics that are hidden, so that only new ones are reported,
+/// e.g. when a lint is turned on in a code base with many existing warnings.
+///
+/// Only warnings and lints (even denied ones) are recorded and hidden, other
+/// errors are always reported. The same fingerprint may be recorded more than
+/// once, each record hides one diagnostic.
+///
+/// The file names are relative to the root of the baseline, which is the
+/// directory of the baseline file when it's loaded.
+///
+/// It's stored as JSON, with the fingerprints sorted to keep diffs small:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "diagnostics": [
+///     {
+///       "code": "unused-binding",
+///       "file": "src/main.sml",
+///       "snippet": "x",
+///       "message": "unused binding `x`"
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baseline {
+    fingerprints: BTreeMap<Fingerprint, usize>,
+
+    /// The canonical directory the file names are relative to.
+    root: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BaselineFile {
+    version: u32,
+    diagnostics: Vec<Fingerprint>,
+}
+
+const BASELINE_VERSION: u32 = 1;
+
+impl Baseline {
+    pub fn new() -> Baseline {
+        Baseline::default()
+    }
+
+    /// Sets the directory the file names are relative to.
+    pub fn with_root(mut self, root: impl AsRef<Path>) -> Baseline {
+        let root = root.as_ref();
+        self.root = Some(fs::canonicalize(root)
+            .or_else(|_| std::path::absolute(root))
+            .unwrap_or_else(|_| root.to_path_buf()));
+        self
+    }
+
+    /// Returns the fingerprint of a diagnostic, with the file name relative to
+    /// the root of the baseline.
+    pub fn fingerprint(&self, diag: &Diagnostic, source_map: Option<&SourceMap>) -> Fingerprint {
+        Fingerprint::new(diag, source_map, self.root.as_deref())
+    }
+
+    /// Returns `true` if the diagnostic can be recorded in a baseline.
+    pub fn is_recordable(diag: &Diagnostic) -> bool {
+        diag.lint.is_some() || diag.level == Level::Warn
+    }
+
+    pub fn insert(&mut self, fingerprint: Fingerprint) {
+        *self.fingerprints.entry(fingerprint).or_default() += 1;
+    }
+
+    /// Removes one record of the fingerprint, returns `false` if there is
+    /// none.
+    pub fn remove(&mut self, fingerprint: &Fingerprint) -> bool {
+        match self.fingerprints.get_mut(fingerprint) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                self.fingerprints.remove(fingerprint);
+            }
+            None => return false,
+        }
+        true
+    }
+
+    /// Returns the number of records.
+    pub fn len(&self) -> usize {
+        self.fingerprints.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fingerprints.is_empty()
+    }
+
+    pub fn from_json(json: &str) -> io::Result<Baseline> {
+        let file: BaselineFile = serde_json::from_str(json)?;
+        if file.version != BASELINE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("unsupported baseline version {}", file.version)));
+        }
+
+        let mut baseline = Baseline::new();
+        for fingerprint in file.diagnostics {
+            baseline.insert(fingerprint);
+        }
+        Ok(baseline)
+    }
+
+    pub fn to_json(&self) -> String {
+        let file = BaselineFile {
+            version: BASELINE_VERSION,
+            diagnostics: self.fingerprints
+                .iter()
+                .flat_map(|(fingerprint, &count)| std::iter::repeat_n(fingerprint.clone(), count))
+                .collect(),
+        };
+        let mut json = serde_json::to_string_pretty(&file)
+            .expect("a baseline can always be serialized");
+        json.push('\n');
+        json
+    }
+
+    /// Loads a baseline file, the file names are relative to its directory.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Baseline> {
+        let path = path.as_ref();
+        Ok(Baseline::from_json(&fs::read_to_string(path)?)?.with_root(Baseline::root_of(path)))
+    }
+
+    /// Returns the directory of a baseline file, the root of its file names.
+    pub fn root_of(path: &Path) -> &Path {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        }
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+}
+
+#[cfg(test)]
+mod baseline_tests {
+    use std::{path::PathBuf, rc::Rc};
+
+    use crate::source::SourceMap;
+    use crate::diagnostic::{DiagnosticEngine, BufferEmitter};
+
+    use super::Baseline;
+
+    fn check(engine: &DiagnosticEngine, file: u32) {
+        engine.create_warn("unused binding `x`").set_primary_span(file + 4..file + 5).emit();
+        engine.create_err("type mismatch").set_primary_span(file + 8..file + 9).emit();
+    }
+
+    #[test]
+    fn test_baseline() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("a.sml".to_string()), "val x = 1\n".to_string());
+        let engine = DiagnosticEngine::with_emitter(
+            Box::new(BufferEmitter::new(Some(source_map))));
+        engine.record_baseline(None);
+        check(&engine, file.start_pos().to_u32());
+        let baseline = engine.take_recorded_baseline();
+        assert_eq!(baseline.len(), 1);
+
+        let baseline = Baseline::from_json(&baseline.to_json()).unwrap();
+
+        // Another file is loaded first, so the positions are different.
+        let source_map = Rc::new(SourceMap::new());
+        source_map.load_test_file(Some("b.sml".to_string()), "val y = 2\n".to_string());
+        let file = source_map.load_test_file(Some("a.sml".to_string()), "val x = 1\n".to_string());
+        let engine = DiagnosticEngine::with_emitter(
+            Box::new(BufferEmitter::new(Some(source_map))));
+        engine.set_baseline(baseline);
+        check(&engine, file.start_pos().to_u32());
+        engine.create_warn("unused binding `x`").emit();
+
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "type mismatch");
+        assert!(diagnostics[1].span().is_dummy());
+    }
+
+    #[test]
+    fn test_baseline_relative_paths() {
+        let dir = std::env::temp_dir().join(format!("kona-baseline-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/a.sml"), "val x = 1\n").unwrap();
+        let baseline_file = dir.join("baseline.json");
+
+        // The same file through two paths relative to the working directory,
+        // which go up to the root, e.g. `/` or `C:\`, and down to the file.
+        let cwd = std::env::current_dir().unwrap();
+        let root = cwd.ancestors().last().unwrap();
+        let Ok(dir_from_root) = dir.strip_prefix(root) else {
+            // The temporary directory is on another drive, no relative path
+            // leads to it.
+            std::fs::remove_dir_all(&dir).unwrap();
+            return;
+        };
+        let up = cwd.strip_prefix(root).unwrap().components().map(|_| "..");
+        let relative = up.collect::<PathBuf>().join(dir_from_root);
+        let paths = [relative.join("src/a.sml"), relative.join("src/../src/./a.sml")];
+        assert!(paths.iter().all(|path| path.is_relative()));
+
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_local_file(paths[0].clone()).unwrap();
+        let engine = DiagnosticEngine::with_emitter(
+            Box::new(BufferEmitter::new(Some(source_map))));
+        engine.record_baseline(Some(Baseline::root_of(&baseline_file)));
+        check(&engine, file.start_pos().to_u32());
+        engine.take_recorded_baseline().write(&baseline_file).unwrap();
+
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_local_file(paths[1].clone()).unwrap();
+        let engine = DiagnosticEngine::with_emitter(
+            Box::new(BufferEmitter::new(Some(source_map))));
+        let baseline = Baseline::load(&baseline_file).unwrap();
+        let json = std::fs::read_to_string(&baseline_file).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(json.contains("\"file\": \"src/a.sml\""), "{}", json);
+        engine.set_baseline(baseline);
+        check(&engine, file.start_pos().to_u32());
+
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "type mismatch");
+    }
+}
@@ -3,35 +3,84 @@
 
 use crate::source::Span;
 
+use super::{CodeSuggestion, Lint};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Diagnostic {
     pub level: Level,
+
+    /// The error code, e.g. `E0042`, its explanation is in the
+    /// [`Registry`](super::Registry).
+    pub code: Option<String>,
+
+    /// The lint that emitted this diagnostic, if any. The level of a lint
+    /// diagnostic is decided by the lint level table on emission.
+    pub lint: Option<&'static Lint>,
+
     pub message: String,
     pub labels: DiagnosticLabels,
+
+    /// Notes and help messages attached to this diagnostic.
+    pub children: Vec<SubDiagnostic>,
+
+    /// Suggested code changes that may fix the problem.
+    pub suggestions: Vec<CodeSuggestion>,
 }
 
 impl Diagnostic {
     pub fn span(&self) -> Span {
         self.labels.primary_label.span
     }
+
+    /// Adds a note without a span, unless the diagnostic already has it, e.g.
+    /// when the engine adds it again to a replayed diagnostic.
+    pub(crate) fn add_note_once(&mut self, message: String) {
+        let exists = self.children
+            .iter()
+            .any(|child| child.level == Level::Note && child.message == message);
+        if !exists {
+            self.children.push(SubDiagnostic {
+                level: Level::Note,
+                message,
+                labels: DiagnosticLabels::default(),
+            });
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Level {
     /// A compilation error.
     ///
-    /// Currently Kona has no error recovery mechanism, all errors are
-    /// considered fatal. It always panic the program when emitting.
+    /// Errors don't stop the compilation by themselves, the engine counts
+    /// them, and the driver calls
+    /// [`DiagnosticEngine::abort_if_errors`](super::DiagnosticEngine::abort_if_errors)
+    /// where it can't go on. The compilation is also aborted once the error
+    /// limit of the engine is reached.
     Error,
 
     /// A warning or lint.
     Warn,
 
-    /// An informational message during compilation.
-    ///
-    /// Unlike "note" diagnostics in most other compilers (e.g. Clang), here we
-    /// use it as a log. This practice will be discarded in the future.
+    /// An additional information, usually attached to another diagnostic as
+    /// a child, e.g. "note: the type of `x` is inferred here".
     Note,
+
+    /// A hint on how to fix the problem, usually attached to another
+    /// diagnostic as a child.
+    Help,
+}
+
+impl Level {
+    /// Returns the name of this level in diagnostic messages, e.g. "error".
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warning",
+            Level::Note => "note",
+            Level::Help => "help",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -45,3 +94,26 @@ pub struct DiagnosticLabel {
     pub span: Span,
     pub message: String,
 }
+
+/// A note or help message attached to a [`Diagnostic`].
+///
+/// A sub-diagnostic may carry its own labels, in which case it is printed with
+/// a source snippet. Otherwise its primary label is dummy, and it is printed
+/// as a one-line footer under its parent, e.g. `= note: ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubDiagnostic {
+    pub level: Level,
+    pub message: String,
+    pub labels: DiagnosticLabels,
+}
+
+impl SubDiagnostic {
+    pub fn span(&self) -> Span {
+        self.labels.primary_label.span
+    }
+
+    /// Returns `true` if this sub-diagnostic has a source snippet.
+    pub fn has_span(&self) -> bool {
+        !self.span().is_dummy()
+    }
+}
@@ -7,7 +7,10 @@ use crate::source::Span;
 
 use crate::{diagnostic::Diagnostic};
 
-use super::{DiagnosticEngine, DiagnosticLabel};
+use super::{
+    DiagnosticEngine, DiagnosticLabel, DiagnosticLabels, Level, SubDiagnostic,
+    Applicability, CodeSuggestion, Substitution,
+};
 
 pub struct DiagnosticBuilder<'a, G: EmissionGuarantee> {
     state: DiagnosticBuilderState<'a>,
@@ -16,7 +19,10 @@ pub struct DiagnosticBuilder<'a, G: EmissionGuarantee> {
 }
 
 impl<'a, G: EmissionGuarantee> DiagnosticBuilder<'a, G> {
-    pub fn new(
+    /// Builders are created by the [`DiagnosticEngine`], e.g.
+    /// [`DiagnosticEngine::create_err`], which picks the guarantee matching
+    /// the level of the diagnostic.
+    pub(crate) fn new(
         engine: &'a DiagnosticEngine,
         diagnostic: Box<Diagnostic>,
     ) -> DiagnosticBuilder<'a, G> {
@@ -27,6 +33,12 @@ impl<'a, G: EmissionGuarantee> DiagnosticBuilder<'a, G> {
         }
     }
 
+    /// Sets the error code, e.g. `E0042`, printed as `error[E0042]: ...`.
+    pub fn set_code(mut self, code: impl Into<String>) -> DiagnosticBuilder<'a, G> {
+        self.diagnostic.code = Some(code.into());
+        self
+    }
+
     pub fn set_primary_label(mut self, span: impl Into<Span>, msg: impl Into<String>) -> DiagnosticBuilder<'a, G> {
         let label = &mut self.diagnostic.labels.primary_label;
         label.span = span.into();
@@ -59,6 +71,67 @@ impl<'a, G: EmissionGuarantee> DiagnosticBuilder<'a, G> {
         self
     }
 
+    /// Attaches a note to the diagnostic, printed as `= note: ...`.
+    pub fn add_note(self, msg: impl Into<String>) -> DiagnosticBuilder<'a, G> {
+        self.add_child(Level::Note, Span::dummy(), msg)
+    }
+
+    /// Attaches a note with a source snippet of the given span.
+    pub fn add_span_note(self, span: impl Into<Span>, msg: impl Into<String>) -> DiagnosticBuilder<'a, G> {
+        self.add_child(Level::Note, span.into(), msg)
+    }
+
+    /// Attaches a help message to the diagnostic, printed as `= help: ...`.
+    pub fn add_help(self, msg: impl Into<String>) -> DiagnosticBuilder<'a, G> {
+        self.add_child(Level::Help, Span::dummy(), msg)
+    }
+
+    /// Attaches a help message with a source snippet of the given span.
+    pub fn add_span_help(self, span: impl Into<Span>, msg: impl Into<String>) -> DiagnosticBuilder<'a, G> {
+        self.add_child(Level::Help, span.into(), msg)
+    }
+
+    fn add_child(mut self, level: Level, span: Span, msg: impl Into<String>) -> DiagnosticBuilder<'a, G> {
+        let mut labels = DiagnosticLabels::default();
+        labels.primary_label.span = span;
+        self.diagnostic.children.push(SubDiagnostic {
+            level,
+            message: msg.into(),
+            labels,
+        });
+        self
+    }
+
+    /// Suggests replacing the source code in `span` with `replacement`.
+    pub fn add_suggestion(
+        self,
+        span: impl Into<Span>,
+        msg: impl Into<String>,
+        replacement: impl Into<String>,
+        applicability: Applicability,
+    ) -> DiagnosticBuilder<'a, G> {
+        self.add_multipart_suggestion(msg, vec![(span.into(), replacement.into())], applicability)
+    }
+
+    /// Suggests a code change consisting of several substitutions, which are
+    /// supposed to be applied together.
+    pub fn add_multipart_suggestion(
+        mut self,
+        msg: impl Into<String>,
+        substitutions: Vec<(Span, String)>,
+        applicability: Applicability,
+    ) -> DiagnosticBuilder<'a, G> {
+        self.diagnostic.suggestions.push(CodeSuggestion {
+            message: msg.into(),
+            substitutions: substitutions
+                .into_iter()
+                .map(|(span, replacement)| Substitution { span, replacement })
+                .collect(),
+            applicability,
+        });
+        self
+    }
+
     pub fn emit(mut self) -> G {
         G::emit_diagnostic_with_guarantee(&mut self)
     }
@@ -74,10 +147,12 @@ impl<'a, G: EmissionGuarantee> DiagnosticBuilder<'a, G> {
 impl<'a, G: EmissionGuarantee> Drop for DiagnosticBuilder<'a, G> {
     fn drop(&mut self) {
         match self.state {
-            DiagnosticBuilderState::Emittable(_) => {
+            // Don't panic again if we are already unwinding, e.g. from a
+            // `FatalError`.
+            DiagnosticBuilderState::Emittable(_) if !std::thread::panicking() => {
                 panic!("`DiagnosticBuilder` was not emitted or cancelled");
             }
-            DiagnosticBuilderState::EmittedOrCancelled => {}
+            _ => {}
         }
     }
 }
@@ -93,7 +168,41 @@ pub trait EmissionGuarantee: Sized {
     ) -> Self;
 }
 
-pub struct EmissionGuaranted;
+/// A proof that an error has been emitted, returned by `emit()` on the
+/// builders of [`DiagnosticEngine::create_err`].
+///
+/// It can't be constructed outside this crate, so a compiler pass returning
+/// `Result<T, EmissionGuaranted>` can only fail after reporting an error:
+///
+/// ```
+/// use kona_diagnostic::diagnostic::{DiagnosticEngine, EmissionGuaranted, SilentEmitter};
+///
+/// fn check_arity(engine: &DiagnosticEngine, args: usize) -> Result<(), EmissionGuaranted> {
+///     if args != 2 {
+///         return Err(engine.create_err("expected 2 arguments").emit());
+///     }
+///     Ok(())
+/// }
+///
+/// let engine = DiagnosticEngine::with_emitter(Box::new(SilentEmitter::new()));
+/// assert!(check_arity(&engine, 3).is_err());
+/// assert!(engine.has_errors());
+/// ```
+///
+/// Failing without an error doesn't compile:
+///
+/// ```compile_fail
+/// use kona_diagnostic::diagnostic::EmissionGuaranted;
+///
+/// fn check_arity(args: usize) -> Result<(), EmissionGuaranted> {
+///     if args != 2 {
+///         return Err(EmissionGuaranted(()));
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmissionGuaranted(());
 
 impl EmissionGuarantee for EmissionGuaranted {
     fn emit_diagnostic_with_guarantee(
@@ -101,9 +210,9 @@ impl EmissionGuarantee for EmissionGuaranted {
     ) -> Self {
         match db.state {
             DiagnosticBuilderState::Emittable(engine) => {
-                engine.emit_diagnostic(db.diagnostic.as_ref());
                 db.state = DiagnosticBuilderState::EmittedOrCancelled;
-                EmissionGuaranted
+                engine.emit_diagnostic(db.diagnostic.as_ref());
+                EmissionGuaranted(())
             }
 
             DiagnosticBuilderState::EmittedOrCancelled => {
@@ -1,25 +1,70 @@
 // Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
 // root for license information.
 
-use std::{rc::Rc, sync::Mutex};
+use std::{
+    rc::Rc,
+    io::{self, Write},
+    sync::{Mutex, MutexGuard, PoisonError},
+    collections::HashMap,
+    path::Path,
+};
 
 use crate::source::SourceMap;
 
 use crate::diagnostic::{TtyEmitter, Emitter, DiagnosticBuilder, Diagnostic, Level};
+use crate::diagnostic::emitter::{render_diagnostic, RenderConfig};
 
-use super::DiagnosticLabels;
+use super::{
+    DiagnosticLabels, Applicability, CodeSuggestion, FatalError, EmissionGuaranted, Registry,
+    Lint, LintLevel, LintLevels, Suppression, Suppressions, Baseline,
+};
 
 pub struct DiagnosticEngine {
     pub(crate) inner: Mutex<DiagnosticEngineInner>,
+
+    /// The explanations of the error codes, every emitted code should be
+    /// registered if it is set. It's read-only, so it's kept out of the lock.
+    registry: Option<Registry>,
 }
 
 pub struct DiagnosticEngineInner {
-    // NOTE: I was going to use `Vec<Box<dyn Emitter>>` here, but then I
-    // realized that the composability of emitters is more important than
-    // looping through multiple separate emitters. We should implement
-    // something like `emitter1.compose(emitter2)` instead of just
-    // `vec![emitter1, emitter2]`.
+    /// The emitter, use the combinators on [`Emitter`] (e.g. [`Emitter::tee`])
+    /// to emit to more than one place.
     emitter: Box<dyn Emitter>,
+
+    /// Machine-applicable suggestions of all emitted diagnostics, collected
+    /// for tools that fix the code automatically.
+    applicable_suggestions: Vec<CodeSuggestion>,
+
+    /// The number of emitted diagnostics of each level. Children are not
+    /// counted.
+    counts: HashMap<Level, usize>,
+
+    /// The compilation is aborted once this many errors are emitted.
+    error_limit: Option<usize>,
+
+    /// The registered lints and their levels.
+    lint_levels: LintLevels,
+
+    /// Lints suppressed in the source code.
+    suppressions: Suppressions,
+
+    /// Known warnings and lints that are hidden.
+    baseline: Option<Baseline>,
+
+    /// The fingerprints of the emitted warnings and lints, if they are being
+    /// recorded for a new baseline.
+    recorded_baseline: Option<Baseline>,
+
+    /// Whether [`Emitter::finish`] was called.
+    finished: bool,
+
+    /// The first I/O error of the emitter.
+    emit_error: Option<io::Error>,
+
+    /// Whether the output of the emitter is closed, e.g. the reader of the
+    /// pipe has exited. Diagnostics are still counted but not emitted.
+    output_closed: bool,
 }
 
 impl DiagnosticEngine {
@@ -27,41 +72,405 @@ impl DiagnosticEngine {
         DiagnosticEngine {
             inner: Mutex::new(DiagnosticEngineInner {
                 emitter,
-            })
+                applicable_suggestions: vec![],
+                counts: HashMap::new(),
+                error_limit: None,
+                lint_levels: LintLevels::default(),
+                suppressions: Suppressions::default(),
+                baseline: None,
+                recorded_baseline: None,
+                finished: false,
+                emit_error: None,
+                output_closed: false,
+            }),
+            registry: None,
         }
     }
 
+    /// Locks the inner state. A panic while holding the lock (e.g. in an
+    /// emitter) doesn't make the engine unusable, the state is still
+    /// consistent enough to report errors.
+    fn lock(&self) -> MutexGuard<'_, DiagnosticEngineInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn inner_mut(&mut self) -> &mut DiagnosticEngineInner {
+        self.inner.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Aborts the compilation once `limit` errors are emitted, there is no
+    /// limit by default.
+    pub fn with_error_limit(mut self, limit: usize) -> DiagnosticEngine {
+        self.inner_mut().error_limit = Some(limit);
+        self
+    }
+
+    /// Sets the registry of error codes. Emitting a diagnostic whose code is
+    /// not registered is a bug, the diagnostic gets a note saying so.
+    pub fn with_registry(mut self, registry: Registry) -> DiagnosticEngine {
+        self.registry = Some(registry);
+        self
+    }
+
     pub fn with_tty_emitter(source_map: Rc<SourceMap>) -> DiagnosticEngine {
         DiagnosticEngine::with_emitter(
             Box::new(TtyEmitter::new(source_map)),
         )
     }
 
-    pub fn create_diagnostic(&self, level: Level, msg: impl Into<String>) -> DiagnosticBuilder<()> {
-        DiagnosticBuilder::new(
-            self,
-            Box::new(Diagnostic {
-                level,
-                message: msg.into(),
-                labels: DiagnosticLabels::default(),
-            }),
-        )
+    /// Creates a diagnostic of any level. Use [`create_err`](Self::create_err)
+    /// for errors to get an [`EmissionGuaranted`] on emission.
+    pub fn create_diagnostic(&self, level: Level, msg: impl Into<String>) -> DiagnosticBuilder<'_, ()> {
+        DiagnosticBuilder::new(self, new_diagnostic(level, msg))
     }
 
-    pub fn create_err(&self, msg: impl Into<String>) -> DiagnosticBuilder<()> {
-        self.create_diagnostic(Level::Error, msg)
+    pub fn create_err(&self, msg: impl Into<String>) -> DiagnosticBuilder<'_, EmissionGuaranted> {
+        DiagnosticBuilder::new(self, new_diagnostic(Level::Error, msg))
     }
 
-    pub fn create_warn(&self, msg: impl Into<String>) -> DiagnosticBuilder<()> {
+    pub fn create_warn(&self, msg: impl Into<String>) -> DiagnosticBuilder<'_, ()> {
         self.create_diagnostic(Level::Warn, msg)
     }
 
-    pub fn create_note(&self, msg: impl Into<String>) -> DiagnosticBuilder<()> {
+    /// Creates a diagnostic of a lint, its level is decided by the lint level
+    /// table on emission, and it's dropped if the lint is allowed.
+    pub fn create_lint(&self, lint: &'static Lint, msg: impl Into<String>) -> DiagnosticBuilder<'_, ()> {
+        let mut diagnostic = new_diagnostic(Level::Warn, msg);
+        diagnostic.lint = Some(lint);
+        DiagnosticBuilder::new(self, diagnostic)
+    }
+
+    pub fn create_note(&self, msg: impl Into<String>) -> DiagnosticBuilder<'_, ()> {
         self.create_diagnostic(Level::Note, msg)
     }
 
     pub fn emit_diagnostic(&self, diagnostic: &Diagnostic) {
-        let mut inner = self.inner.lock().unwrap();
-        inner.emitter.emit_diagnostic(diagnostic)
+        // Reported like an internal error rather than panicking, the run goes
+        // on with a usable engine.
+        let unregistered;
+        let diagnostic = match (&self.registry, &diagnostic.code) {
+            (Some(registry), Some(code)) if !registry.is_registered(code) => {
+                let mut diagnostic = diagnostic.clone();
+                diagnostic.add_note_once(format!(
+                    "error code `{}` has no explanation in the registry, this is a bug", code));
+                unregistered = diagnostic;
+                &unregistered
+            }
+            _ => diagnostic,
+        };
+
+        let mut guard = self.lock();
+        let inner = &mut *guard;
+        if inner.suppressions.suppress(diagnostic, &inner.lint_levels) {
+            return;
+        }
+        let recordable = Baseline::is_recordable(diagnostic);
+        let diagnostic = match inner.lint_levels.apply(diagnostic) {
+            Some(diagnostic) => diagnostic,
+            None => return,
+        };
+        let diagnostic: &Diagnostic = &diagnostic;
+
+        if recordable && (inner.baseline.is_some() || inner.recorded_baseline.is_some()) {
+            let source_map = inner.emitter.source_map();
+            if let Some(recorded) = &mut inner.recorded_baseline {
+                recorded.insert(recorded.fingerprint(diagnostic, source_map.as_deref()));
+            }
+            if let Some(baseline) = &mut inner.baseline {
+                if baseline.remove(&baseline.fingerprint(diagnostic, source_map.as_deref())) {
+                    return;
+                }
+            }
+        }
+
+        inner.applicable_suggestions.extend(diagnostic.suggestions
+            .iter()
+            .filter(|suggestion| {
+                suggestion.applicability == Applicability::MachineApplicable
+            })
+            .cloned());
+        inner.emit(diagnostic);
+        *inner.counts.entry(diagnostic.level).or_default() += 1;
+
+        let limit_reached = diagnostic.level == Level::Error
+            && inner.error_limit.is_some_and(|limit| inner.count(Level::Error) >= limit);
+        // Release the lock before unwinding, or the mutex is poisoned.
+        drop(guard);
+        if limit_reached {
+            self.abort();
+        }
+    }
+
+    /// Registers the lints, so that their levels can be set by name.
+    pub fn register_lints(&self, lints: &[&'static Lint]) {
+        self.lock().lint_levels.register(lints)
+    }
+
+    /// Finds a registered lint by name.
+    pub fn find_lint(&self, name: &str) -> Option<&'static Lint> {
+        self.lock().lint_levels.find(name)
+    }
+
+    /// Sets the level of a lint, or of all warnings if `name` is
+    /// [`WARNINGS`](super::WARNINGS). Later calls override earlier ones,
+    /// except that a forbidden lint stays forbidden.
+    pub fn set_lint_level(&self, name: &str, level: LintLevel) {
+        self.lock().lint_levels.set(name, level)
+    }
+
+    /// Returns the current level of a lint.
+    pub fn lint_level(&self, lint: &Lint) -> LintLevel {
+        self.lock().lint_levels.level(lint)
+    }
+
+    /// Suppresses a lint in a span of the source code, see [`Suppression`].
+    pub fn add_suppression(&self, suppression: Suppression) {
+        self.lock().suppressions.add(suppression)
+    }
+
+    /// Returns the suppressions that haven't suppressed any diagnostic so far,
+    /// call it after all lints are emitted to report them.
+    pub fn unused_suppressions(&self) -> Vec<Suppression> {
+        self.lock().suppressions.unused()
+    }
+
+    /// Hides the warnings and lints recorded in the baseline, see
+    /// [`Baseline`].
+    pub fn set_baseline(&self, baseline: Baseline) {
+        self.lock().baseline = Some(baseline);
+    }
+
+    /// Starts recording the fingerprints of the emitted warnings and lints,
+    /// including the ones hidden by the current baseline. The file names are
+    /// relative to `root`, usually the directory of the baseline file.
+    pub fn record_baseline(&self, root: Option<&Path>) {
+        self.lock().recorded_baseline.get_or_insert_with(|| match root {
+            Some(root) => Baseline::new().with_root(root),
+            None => Baseline::new(),
+        });
+    }
+
+    /// Takes the baseline recorded since [`record_baseline`](Self::record_baseline),
+    /// and stops recording.
+    pub fn take_recorded_baseline(&self) -> Baseline {
+        self.lock().recorded_baseline.take().unwrap_or_default()
+    }
+
+    /// Returns the number of emitted diagnostics of the given level.
+    pub fn count(&self, level: Level) -> usize {
+        self.lock().count(level)
+    }
+
+    pub fn err_count(&self) -> usize {
+        self.count(Level::Error)
+    }
+
+    pub fn warn_count(&self) -> usize {
+        self.count(Level::Warn)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.err_count() > 0
+    }
+
+    /// Aborts the compilation with a summary if any error has been emitted.
+    pub fn abort_if_errors(&self) {
+        if self.has_errors() {
+            self.abort();
+        }
+    }
+
+    /// Emits the summary, e.g. "aborting due to 3 previous errors; 2 warnings
+    /// emitted", finishes the emitter and raises a [`FatalError`].
+    fn abort(&self) -> ! {
+        let mut inner = self.lock();
+        let summary = inner.abort_summary();
+        inner.emit(&new_diagnostic(Level::Error, summary));
+        inner.finish();
+        drop(inner);
+        FatalError.raise()
+    }
+
+    /// Tells the emitter that the compilation is finished, emitters writing a
+    /// single document for all diagnostics write it out now.
+    /// It does nothing if the emitter is already finished, e.g. when the
+    /// compilation was aborted.
+    pub fn finish(&self) {
+        self.lock().finish()
+    }
+
+    /// Takes the first I/O error of the emitter, if any.
+    ///
+    /// Emission never panics: if the output is closed (`BrokenPipe`, e.g.
+    /// `kona check | head`), the following diagnostics are silently dropped,
+    /// and on other errors the diagnostic is written to stderr as plain text.
+    pub fn take_emit_error(&self) -> Option<io::Error> {
+        self.lock().emit_error.take()
+    }
+
+    /// Takes the diagnostics stored by the emitter, if it is (or contains) a
+    /// [`BufferEmitter`](super::BufferEmitter).
+    pub fn drain_diagnostics(&self) -> Vec<Diagnostic> {
+        let mut inner = self.lock();
+        inner.emitter.drain_diagnostics()
+    }
+
+    /// Emits the diagnostics again, e.g. the ones drained from the engine of
+    /// a speculative pass.
+    pub fn replay_diagnostics(&self, diagnostics: impl IntoIterator<Item = Diagnostic>) {
+        for diagnostic in diagnostics {
+            self.emit_diagnostic(&diagnostic);
+        }
+    }
+
+    /// Takes the machine-applicable suggestions of all diagnostics emitted so
+    /// far, see also [`Fixes`](super::Fixes).
+    pub fn take_applicable_suggestions(&self) -> Vec<CodeSuggestion> {
+        let mut inner = self.lock();
+        std::mem::take(&mut inner.applicable_suggestions)
+    }
+}
+
+fn new_diagnostic(level: Level, msg: impl Into<String>) -> Box<Diagnostic> {
+    Box::new(Diagnostic {
+        level,
+        code: None,
+        lint: None,
+        message: msg.into(),
+        labels: DiagnosticLabels::default(),
+        children: vec![],
+        suggestions: vec![],
+    })
+}
+
+impl DiagnosticEngineInner {
+    fn count(&self, level: Level) -> usize {
+        self.counts.get(&level).copied().unwrap_or(0)
+    }
+
+    fn finish(&mut self) {
+        if !self.finished && !self.output_closed {
+            self.finished = true;
+            if let Err(err) = self.emitter.finish() {
+                self.record_emit_error(err, None);
+            }
+        }
+    }
+
+    /// Emits a diagnostic with the emitter, and handles its I/O errors.
+    fn emit(&mut self, diag: &Diagnostic) {
+        if self.output_closed {
+            return;
+        }
+        if let Err(err) = self.emitter.emit_diagnostic(diag) {
+            self.record_emit_error(err, Some(diag));
+        }
+    }
+
+    fn record_emit_error(&mut self, err: io::Error, diag: Option<&Diagnostic>) {
+        if err.kind() == io::ErrorKind::BrokenPipe {
+            self.output_closed = true;
+        } else if let Some(diag) = diag {
+            // Don't lose the diagnostic, it's written as plain text as the
+            // last resort. There is nothing else we can do if this fails.
+            let text = render_diagnostic(diag, self.emitter.source_map().as_deref(),
+                &RenderConfig::default());
+            let _ = io::stderr().write_all(text.to_plain_string().as_bytes());
+        }
+        self.emit_error.get_or_insert(err);
+    }
+
+    fn abort_summary(&self) -> String {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+
+        let errors = self.count(Level::Error);
+        let mut summary = format!("aborting due to {} previous error{}", errors, plural(errors));
+        let warnings = self.count(Level::Warn);
+        if warnings > 0 {
+            summary.push_str(&format!("; {} warning{} emitted", warnings, plural(warnings)));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod diagnostic_engine_tests {
+    use std::io;
+
+    use crate::diagnostic::{BufferEmitter, FatalError, JsonEmitter, Level, Registry};
+
+    use super::DiagnosticEngine;
+
+    #[test]
+    fn test_error_limit() {
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)))
+            .with_error_limit(3);
+
+        engine.create_warn("unused variable").emit();
+        engine.abort_if_errors();
+        engine.create_err("first").emit();
+        engine.create_warn("unused function").emit();
+        engine.create_err("second").emit();
+        assert!(engine.has_errors());
+        assert_eq!(engine.err_count(), 2);
+        assert_eq!(engine.warn_count(), 2);
+
+        let result = FatalError::catch(|| {
+            engine.create_err("third").emit();
+            engine.create_err("unreachable").emit();
+        });
+        assert_eq!(result, Err(FatalError));
+
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics.len(), 6);
+        let summary = diagnostics.last().unwrap();
+        assert_eq!(summary.level, Level::Error);
+        assert_eq!(summary.message, "aborting due to 3 previous errors; 2 warnings emitted");
+        assert_eq!(engine.err_count(), 3);
+    }
+
+    #[test]
+    fn test_unregistered_code() {
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)))
+            .with_registry(Registry::new(&[("E0001", "An explanation.")]));
+
+        engine.create_err("registered").set_code("E0001").emit();
+        engine.create_err("unregistered").set_code("E9999").emit();
+        engine.create_warn("after").emit();
+
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics[0].children.is_empty());
+        assert_eq!(diagnostics[1].children[0].message,
+            "error code `E9999` has no explanation in the registry, this is a bug");
+        assert_eq!(engine.err_count(), 2);
+
+        engine.replay_diagnostics(diagnostics.clone());
+        assert_eq!(engine.drain_diagnostics(), diagnostics);
+    }
+
+    #[test]
+    fn test_broken_pipe() {
+        struct ClosedPipe;
+
+        impl io::Write for ClosedPipe {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let engine = DiagnosticEngine::with_emitter(
+            Box::new(JsonEmitter::new(Box::new(ClosedPipe), None)));
+        engine.create_err("first").emit();
+        engine.create_err("second").emit();
+        engine.finish();
+
+        assert_eq!(engine.err_count(), 2);
+        assert_eq!(engine.take_emit_error().map(|err| err.kind()), Some(io::ErrorKind::BrokenPipe));
+        assert!(engine.take_emit_error().is_none());
     }
 }
@@ -0,0 +1,77 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{rc::Rc, io};
+
+use crate::source::SourceMap;
+
+use crate::diagnostic::Diagnostic;
+
+use super::Emitter;
+
+/// Stores the emitted diagnostics in memory instead of printing them.
+///
+/// Take them out with [`DiagnosticEngine::drain_diagnostics`], e.g. to assert
+/// on them in tests, or to discard or replay the diagnostics of a
+/// speculative pass.
+///
+/// [`DiagnosticEngine::drain_diagnostics`]: crate::diagnostic::DiagnosticEngine::drain_diagnostics
+pub struct BufferEmitter {
+    diagnostics: Vec<Diagnostic>,
+    source_map: Option<Rc<SourceMap>>,
+}
+
+impl BufferEmitter {
+    pub fn new(source_map: Option<Rc<SourceMap>>) -> BufferEmitter {
+        BufferEmitter { diagnostics: vec![], source_map }
+    }
+
+    /// Returns the diagnostics emitted so far.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
+
+impl Emitter for BufferEmitter {
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()> {
+        self.diagnostics.push(diag.clone());
+        Ok(())
+    }
+
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        self.source_map.clone()
+    }
+
+    fn drain_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod buffer_emitter_tests {
+    use crate::diagnostic::{DiagnosticEngine, Level, Emitter, SilentEmitter};
+
+    use super::BufferEmitter;
+
+    #[test]
+    fn test_drain_and_replay() {
+        let speculative = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+        speculative.create_err("type mismatch").emit();
+        speculative.create_warn("unused variable").emit();
+
+        let diagnostics = speculative.drain_diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].level, Level::Error);
+        assert_eq!(diagnostics[1].message, "unused variable");
+        assert!(speculative.drain_diagnostics().is_empty());
+
+        let emitter = BufferEmitter::new(None)
+            .filter_levels(&[Level::Warn])
+            .tee(SilentEmitter::new());
+        let engine = DiagnosticEngine::with_emitter(Box::new(emitter));
+        engine.replay_diagnostics(diagnostics);
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].level, Level::Warn);
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+//! Emitters built from other emitters, see the combinators on [`Emitter`].
+
+use std::{rc::Rc, io};
+
+use crate::source::SourceMap;
+
+use crate::diagnostic::Diagnostic;
+
+use super::Emitter;
+
+/// Forwards every diagnostic to two emitters, created by [`Emitter::tee`].
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> Tee<A, B> {
+    pub(super) fn new(first: A, second: B) -> Tee<A, B> {
+        Tee { first, second }
+    }
+}
+
+impl<A: Emitter, B: Emitter> Emitter for Tee<A, B> {
+    /// Emits to the second emitter even if the first one fails, returns the
+    /// first error.
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()> {
+        let first = self.first.emit_diagnostic(diag);
+        let second = self.second.emit_diagnostic(diag);
+        first.and(second)
+    }
+
+    /// Returns the source map of the first emitter, or of the second one if
+    /// the first has none.
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        self.first.source_map().or_else(|| self.second.source_map())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let first = self.first.finish();
+        let second = self.second.finish();
+        first.and(second)
+    }
+
+    fn drain_diagnostics(&mut self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.first.drain_diagnostics();
+        diagnostics.extend(self.second.drain_diagnostics());
+        diagnostics
+    }
+}
+
+/// Forwards the diagnostics accepted by a predicate, created by
+/// [`Emitter::filter`] and [`Emitter::filter_levels`].
+pub struct Filter<E, P> {
+    emitter: E,
+    predicate: P,
+}
+
+impl<E, P> Filter<E, P> {
+    pub(super) fn new(emitter: E, predicate: P) -> Filter<E, P> {
+        Filter { emitter, predicate }
+    }
+}
+
+impl<E, P> Emitter for Filter<E, P>
+where
+    E: Emitter,
+    P: FnMut(&Diagnostic) -> bool,
+{
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()> {
+        if (self.predicate)(diag) {
+            self.emitter.emit_diagnostic(diag)?;
+        }
+        Ok(())
+    }
+
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        self.emitter.source_map()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.emitter.finish()
+    }
+
+    fn drain_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.emitter.drain_diagnostics()
+    }
+}
+
+/// Transforms each diagnostic before forwarding it, created by
+/// [`Emitter::map`].
+pub struct Map<E, F> {
+    emitter: E,
+    f: F,
+}
+
+impl<E, F> Map<E, F> {
+    pub(super) fn new(emitter: E, f: F) -> Map<E, F> {
+        Map { emitter, f }
+    }
+}
+
+impl<E, F> Emitter for Map<E, F>
+where
+    E: Emitter,
+    F: FnMut(&mut Diagnostic),
+{
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()> {
+        let mut diag = diag.clone();
+        (self.f)(&mut diag);
+        self.emitter.emit_diagnostic(&diag)
+    }
+
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        self.emitter.source_map()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.emitter.finish()
+    }
+
+    fn drain_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.emitter.drain_diagnostics()
+    }
+}
+
+#[cfg(test)]
+mod combinators_tests {
+    use std::rc::Rc;
+
+    use crate::source::SourceMap;
+    use crate::diagnostic::{DiagnosticEngine, JsonEmitter, Level, Emitter};
+
+    use super::super::test_utils::SharedBuffer;
+
+    #[test]
+    fn test_tee_filter_map() {
+        let source_map = Rc::new(SourceMap::new());
+        let all = SharedBuffer::default();
+        let errors = SharedBuffer::default();
+
+        let emitter = JsonEmitter::new(Box::new(all.clone()), Some(source_map.clone()))
+            .map(|diag| diag.message = diag.message.to_uppercase())
+            .tee(JsonEmitter::new(Box::new(errors.clone()), Some(source_map))
+                .filter_levels(&[Level::Error]));
+        let engine = DiagnosticEngine::with_emitter(Box::new(emitter));
+
+        engine.create_warn("unused variable").emit();
+        engine.create_err("type mismatch").emit();
+
+        let all = all.contents();
+        let all = all.lines().collect::<Vec<_>>();
+        assert_eq!(all.len(), 2);
+        assert!(all[0].contains("\"message\":\"UNUSED VARIABLE\""));
+
+        let errors = errors.contents();
+        let errors = errors.lines().collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("\"message\":\"type mismatch\""));
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{rc::Rc, io::{self, Write}};
+
+use serde::Serialize;
+
+use crate::source::{SourceMap, Span};
+
+use crate::diagnostic::{Diagnostic, DiagnosticLabels, SubDiagnostic, CodeSuggestion};
+
+use super::{Emitter, RenderConfig};
+use super::tty_emitter::render_diagnostic;
+
+/// Writes diagnostics as JSON, one object per line.
+///
+/// Each diagnostic looks like this (formatted for readability):
+///
+/// ```json
+/// {
+///   "level": "error",
+///   "code": "E0042",
+///   "message": "unexpected token keyword `in` in case-of expression",
+///   "labels": [
+///     {
+///       "message": "expect keyword `of`",
+///       "primary": true,
+///       "byte_start": 140,
+///       "byte_end": 142,
+///       "file_name": "example.sml",
+///       "line_start": 5,
+///       "line_end": 5,
+///       "column_start": 11,
+///       "column_end": 13,
+///       "display_column_start": 11,
+///       "display_column_end": 13
+///     }
+///   ],
+///   "children": [],
+///   "suggestions": [],
+///   "rendered": "error: unexpected token keyword `in` in case-of expression\n..."
+/// }
+/// ```
+///
+/// Byte offsets are relative to the start of the file, lines and columns are
+/// 1-based. If the span cannot be resolved in the source map (or there is no
+/// source map at all), the byte offsets are the raw positions, and the file
+/// name, lines and columns are `null`.
+pub struct JsonEmitter {
+    out: Box<dyn Write>,
+    source_map: Option<Rc<SourceMap>>,
+    config: RenderConfig,
+}
+
+impl JsonEmitter {
+    pub fn new(out: Box<dyn Write>, source_map: Option<Rc<SourceMap>>) -> JsonEmitter {
+        JsonEmitter { out, source_map, config: RenderConfig::default() }
+    }
+
+    pub fn stdout(source_map: Option<Rc<SourceMap>>) -> JsonEmitter {
+        JsonEmitter::new(Box::new(io::stdout()), source_map)
+    }
+
+    /// Sets how the `rendered` field is rendered.
+    pub fn with_render_config(mut self, config: RenderConfig) -> JsonEmitter {
+        self.config = config;
+        self
+    }
+}
+
+impl Emitter for JsonEmitter {
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()> {
+        let json = JsonDiagnostic::from_diagnostic(diag, self.source_map.as_deref(), &self.config);
+        serde_json::to_writer(&mut self.out, &json)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        self.source_map.clone()
+    }
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    level: &'static str,
+
+    /// The error code, `null` if there is none and for children.
+    code: Option<&'a str>,
+    message: &'a str,
+    labels: Vec<JsonLabel<'a>>,
+    children: Vec<JsonDiagnostic<'a>>,
+    suggestions: Vec<JsonSuggestion<'a>>,
+
+    /// The diagnostic rendered as the TTY emitter does, without colors. It is
+    /// `null` for children.
+    rendered: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonLabel<'a> {
+    message: Option<&'a str>,
+    primary: bool,
+
+    #[serde(flatten)]
+    span: JsonSpan,
+}
+
+#[derive(Serialize)]
+struct JsonSuggestion<'a> {
+    message: &'a str,
+    applicability: &'static str,
+    substitutions: Vec<JsonSubstitution<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonSubstitution<'a> {
+    replacement: &'a str,
+
+    #[serde(flatten)]
+    span: JsonSpan,
+}
+
+#[derive(Serialize)]
+struct JsonSpan {
+    byte_start: u32,
+    byte_end: u32,
+    file_name: Option<String>,
+    line_start: Option<usize>,
+    line_end: Option<usize>,
+    column_start: Option<usize>,
+    column_end: Option<usize>,
+    display_column_start: Option<usize>,
+    display_column_end: Option<usize>,
+}
+
+impl<'a> JsonDiagnostic<'a> {
+    fn from_diagnostic(
+        diag: &'a Diagnostic,
+        source_map: Option<&SourceMap>,
+        config: &RenderConfig,
+    ) -> JsonDiagnostic<'a> {
+        JsonDiagnostic {
+            level: diag.level.as_str(),
+            code: diag.code.as_deref(),
+            message: &diag.message,
+            labels: JsonLabel::from_labels(&diag.labels, source_map),
+            children: diag.children
+                .iter()
+                .map(|child| JsonDiagnostic::from_child(child, source_map))
+                .collect(),
+            suggestions: diag.suggestions
+                .iter()
+                .map(|suggestion| JsonSuggestion::from_suggestion(suggestion, source_map))
+                .collect(),
+            rendered: Some(render_diagnostic(diag, source_map, config).to_plain_string()),
+        }
+    }
+
+    fn from_child(child: &'a SubDiagnostic, source_map: Option<&SourceMap>) -> JsonDiagnostic<'a> {
+        JsonDiagnostic {
+            level: child.level.as_str(),
+            code: None,
+            message: &child.message,
+            labels: JsonLabel::from_labels(&child.labels, source_map),
+            children: vec![],
+            suggestions: vec![],
+            rendered: None,
+        }
+    }
+}
+
+impl<'a> JsonLabel<'a> {
+    fn from_labels(labels: &'a DiagnosticLabels, source_map: Option<&SourceMap>) -> Vec<JsonLabel<'a>> {
+        std::iter::once((&labels.primary_label, true))
+            .chain(labels.sublabels.iter().map(|label| (label, false)))
+            .filter(|(label, _)| !label.span.is_dummy())
+            .map(|(label, primary)| JsonLabel {
+                message: Some(label.message.as_str()).filter(|msg| !msg.is_empty()),
+                primary,
+                span: JsonSpan::from_span(label.span, source_map),
+            })
+            .collect()
+    }
+}
+
+impl<'a> JsonSuggestion<'a> {
+    fn from_suggestion(suggestion: &'a CodeSuggestion, source_map: Option<&SourceMap>) -> JsonSuggestion<'a> {
+        JsonSuggestion {
+            message: &suggestion.message,
+            applicability: suggestion.applicability.as_str(),
+            substitutions: suggestion.substitutions
+                .iter()
+                .map(|sub| JsonSubstitution {
+                    replacement: &sub.replacement,
+                    span: JsonSpan::from_span(sub.span, source_map),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl JsonSpan {
+    fn from_span(span: Span, source_map: Option<&SourceMap>) -> JsonSpan {
+        let resolved = source_map.and_then(|source_map| {
+            let file = source_map.lookup_file_at_span(span).ok()?;
+            let start = source_map.lookup_start_pos_info(span).ok()?;
+            let end = source_map.lookup_end_pos_info(span).ok()?;
+            Some((file, start, end))
+        });
+
+        match resolved {
+            Some((file, start, end)) => {
+                let offset = file.start_pos().to_u32();
+                JsonSpan {
+                    byte_start: span.start().to_u32() - offset,
+                    byte_end: span.end().to_u32() - offset,
+                    file_name: Some(file.name()),
+                    line_start: Some(start.line()),
+                    line_end: Some(end.line()),
+                    column_start: Some(start.col() + 1),
+                    column_end: Some(end.col() + 1),
+                    display_column_start: Some(start.col_display() + 1),
+                    display_column_end: Some(end.col_display() + 1),
+                }
+            }
+            None => JsonSpan {
+                byte_start: span.start().to_u32(),
+                byte_end: span.end().to_u32(),
+                file_name: None,
+                line_start: None,
+                line_end: None,
+                column_start: None,
+                column_end: None,
+                display_column_start: None,
+                display_column_end: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod json_emitter_tests {
+    use std::rc::Rc;
+
+    use crate::source::SourceMap;
+    use crate::diagnostic::{DiagnosticEngine, Applicability};
+
+    use super::JsonEmitter;
+    use super::super::test_utils::SharedBuffer;
+
+    #[test]
+    fn test_json_emitter() {
+        let source_map = Rc::new(SourceMap::new());
+        source_map.load_test_file(Some("first.sml".to_string()), "val x = 1\n".to_string());
+        let file = source_map.load_test_file(
+            Some("second.sml".to_string()),
+            "fun f n =\n  case n in _ => 0\n".to_string(),
+        );
+        let start = file.start_pos().to_u32();
+
+        let buffer = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(
+            Box::new(JsonEmitter::new(Box::new(buffer.clone()), Some(source_map))),
+        );
+        engine.create_err("unexpected token")
+            .set_code("E0042")
+            .set_primary_label(start + 19..start + 21, "expect keyword `of`")
+            .add_note("a note")
+            .add_suggestion(start + 19..start + 21, "replace `in` with `of`", "of",
+                Applicability::MachineApplicable)
+            .emit();
+
+        let output = buffer.contents();
+        assert_eq!(output.lines().count(), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(json["level"], "error");
+        assert_eq!(json["code"], "E0042");
+        assert_eq!(json["labels"][0]["message"], "expect keyword `of`");
+        assert_eq!(json["labels"][0]["file_name"], "second.sml");
+        assert_eq!(json["labels"][0]["byte_start"], 19);
+        assert_eq!(json["labels"][0]["line_start"], 2);
+        assert_eq!(json["labels"][0]["column_start"], 10);
+        assert_eq!(json["labels"][0]["column_end"], 12);
+        assert_eq!(json["children"][0]["level"], "note");
+        assert_eq!(json["children"][0]["rendered"], serde_json::Value::Null);
+        assert_eq!(json["suggestions"][0]["applicability"], "machine-applicable");
+        assert_eq!(json["suggestions"][0]["substitutions"][0]["replacement"], "of");
+        assert!(json["rendered"].as_str().unwrap().starts_with("error[E0042]: unexpected token\n"));
+    }
+
+    #[test]
+    fn test_span_at_end_of_file() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("test.sml".to_string()), "val x =\n".to_string());
+        source_map.load_test_file(Some("other.sml".to_string()), "val y = 2\n".to_string());
+        let end = file.end_pos().to_u32();
+
+        let buffer = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(
+            Box::new(JsonEmitter::new(Box::new(buffer.clone()), Some(source_map))),
+        );
+        engine.create_err("unexpected end of file").set_primary_label(end..end, "").emit();
+
+        let json: serde_json::Value = serde_json::from_str(&buffer.contents()).unwrap();
+        let label = &json["labels"][0];
+        assert_eq!(label["file_name"], "test.sml");
+        assert_eq!(label["byte_start"], 8);
+        assert_eq!((&label["line_start"], &label["line_end"]), (&1.into(), &1.into()));
+        assert_eq!((&label["column_start"], &label["column_end"]), (&9.into(), &9.into()));
+    }
+}
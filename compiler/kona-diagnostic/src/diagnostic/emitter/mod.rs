@@ -0,0 +1,168 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+//! Emitters that print or export the diagnostics.
+
+mod buffer_emitter;
+mod combinators;
+mod json_emitter;
+mod render_config;
+mod sarif_emitter;
+mod short_emitter;
+mod snippet;
+mod styled_buffer;
+mod tty_emitter;
+
+pub use buffer_emitter::*;
+pub use combinators::*;
+pub use json_emitter::*;
+pub use render_config::*;
+pub use sarif_emitter::*;
+pub use short_emitter::*;
+pub use tty_emitter::*;
+
+pub(crate) use tty_emitter::render_diagnostic;
+
+use std::{rc::Rc, io};
+
+use crate::source::SourceMap;
+
+use crate::diagnostic::{Diagnostic, Level};
+
+/// Emitters print or export the diagnostics emitted by a
+/// [`DiagnosticEngine`](super::DiagnosticEngine).
+///
+/// Emitters compose: `tty.tee(json)` emits to both,
+/// `json.filter_levels(&[Level::Error])` drops everything but errors, and
+/// `emitter.map(f)` rewrites the diagnostics before emitting them.
+pub trait Emitter {
+    /// Emit a diagnostic.
+    ///
+    /// I/O errors are returned to the [`DiagnosticEngine`](super::DiagnosticEngine),
+    /// which records them instead of panicking in the middle of error
+    /// reporting.
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()>;
+
+    // TBD: Why we put `SourceMap` in `Emitter`, not `DiagnosticEngine`?
+
+    /// Returns the `SourceMap` associated with this emitter if any.
+    ///
+    /// Not all emitters need a source map, for example, a JSON emitter can
+    /// just report the position index in the span, it doesn't need to know
+    /// the actual source code there.
+    fn source_map(&self) -> Option<Rc<SourceMap>>;
+
+    /// Called once after all diagnostics of a compilation are emitted.
+    ///
+    /// Emitters that write a single document for the whole compilation (e.g.
+    /// SARIF) buffer the diagnostics and write them out here.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Takes the diagnostics stored by the emitter, see [`BufferEmitter`].
+    /// Emitters that don't store diagnostics return an empty vector.
+    fn drain_diagnostics(&mut self) -> Vec<Diagnostic> {
+        vec![]
+    }
+
+    /// Emits every diagnostic to both `self` and `other`, in that order.
+    fn tee<E: Emitter>(self, other: E) -> Tee<Self, E>
+    where
+        Self: Sized,
+    {
+        Tee::new(self, other)
+    }
+
+    /// Emits only the diagnostics for which `predicate` returns `true`.
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+    where
+        Self: Sized,
+        P: FnMut(&Diagnostic) -> bool,
+    {
+        Filter::new(self, predicate)
+    }
+
+    /// Emits only the diagnostics of the given levels.
+    fn filter_levels(self, levels: &[Level]) -> Filter<Self, impl FnMut(&Diagnostic) -> bool>
+    where
+        Self: Sized,
+    {
+        let levels = levels.to_vec();
+        Filter::new(self, move |diag: &Diagnostic| levels.contains(&diag.level))
+    }
+
+    /// Transforms each diagnostic with `f` before emitting it. The diagnostic
+    /// is cloned, other emitters are not affected.
+    fn map<F>(self, f: F) -> Map<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(&mut Diagnostic),
+    {
+        Map::new(self, f)
+    }
+}
+
+impl<E: Emitter + ?Sized> Emitter for Box<E> {
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()> {
+        (**self).emit_diagnostic(diag)
+    }
+
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        (**self).source_map()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+
+    fn drain_diagnostics(&mut self) -> Vec<Diagnostic> {
+        (**self).drain_diagnostics()
+    }
+}
+
+/// Discards all diagnostics.
+#[derive(Default)]
+pub struct SilentEmitter {}
+
+impl SilentEmitter {
+    pub fn new() -> SilentEmitter {
+        SilentEmitter {}
+    }
+}
+
+impl Emitter for SilentEmitter {
+    fn emit_diagnostic(&mut self, _diag: &Diagnostic) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        None
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use std::{rc::Rc, cell::RefCell, io};
+
+    /// An in-memory writer that can be read after being moved into an
+    /// emitter.
+    #[derive(Clone, Default)]
+    pub(crate) struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl SharedBuffer {
+        pub(crate) fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    impl io::Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+/// Options for rendering diagnostics as text, shared by the text-based
+/// emitters, e.g. [`TtyEmitter`](super::TtyEmitter) and the `rendered` field
+/// of [`JsonEmitter`](super::JsonEmitter).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderConfig {
+    /// A multi-line label spanning more lines than this has its middle lines
+    /// elided with `...`. Use `usize::MAX` to never elide.
+    pub max_multiline_lines: usize,
+
+    /// The number of lines kept at the start and at the end of an elided
+    /// multi-line label.
+    pub elided_edge_lines: usize,
+
+    /// The number of unlabeled lines shown before and after each label, to
+    /// give some context. Labels whose context lines overlap are shown
+    /// together.
+    pub context_lines: usize,
+
+    /// The distance between two tab stops. Tabs in the snippets are expanded
+    /// to spaces up to the next tab stop. `None` uses the tab width of the
+    /// source map.
+    pub tab_width: Option<usize>,
+
+    /// The width of the output in columns. Source lines too wide for it are
+    /// cut to a window centered on the labels, with `...` at the cut points.
+    /// The primary label is always in the window, the marks of the labels
+    /// outside it are dropped. `None` never cuts the lines.
+    pub max_width: Option<usize>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> RenderConfig {
+        RenderConfig {
+            max_multiline_lines: 8,
+            elided_edge_lines: 3,
+            context_lines: 0,
+            tab_width: None,
+            max_width: None,
+        }
+    }
+}
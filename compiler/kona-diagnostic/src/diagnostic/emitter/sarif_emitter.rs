@@ -0,0 +1,574 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{rc::Rc, io::{self, Write}, fs, collections::BTreeMap, path::{Path, PathBuf}};
+
+use serde::Serialize;
+
+use crate::source::{SourceMap, SourceFile, Span};
+
+use crate::diagnostic::{Diagnostic, Level, CodeSuggestion};
+
+use super::Emitter;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+const SOURCE_ROOT: &str = "%SRCROOT%";
+
+/// Writes diagnostics as a [SARIF 2.1.0] log.
+///
+/// Unlike the other emitters, a SARIF log is a single document for the whole
+/// run, so the diagnostics are buffered and written out by
+/// [`Emitter::finish`]. Diagnostics emitted after `finish` go to a new log.
+///
+/// Each diagnostic becomes a `result` with its code as the `ruleId`: the
+/// primary label is its location, sublabels and children with spans are its
+/// related locations, and suggestions are its fixes. Children without spans
+/// are appended to the message text, e.g. `note: ...`. Columns are counted in
+/// Unicode code points (`"columnKind": "unicodeCodePoints"`), which are the
+/// same as [`PosInfo::col`](crate::source::PosInfo::col) plus one.
+///
+/// Files under the source root (the current directory by default) have URIs
+/// relative to `%SRCROOT%`, which is given in `originalUriBaseIds`. Other
+/// local files have absolute `file://` URIs.
+///
+/// [SARIF 2.1.0]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+pub struct SarifEmitter {
+    out: Box<dyn Write>,
+    source_map: Option<Rc<SourceMap>>,
+    tool_name: String,
+    tool_version: String,
+    source_root: Option<PathBuf>,
+    results: Vec<SarifResult>,
+}
+
+impl SarifEmitter {
+    pub fn new(out: Box<dyn Write>, source_map: Option<Rc<SourceMap>>) -> SarifEmitter {
+        SarifEmitter {
+            out,
+            source_map,
+            tool_name: "kona".to_string(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            source_root: std::env::current_dir().ok().map(canonicalize),
+            results: vec![],
+        }
+    }
+
+    pub fn stdout(source_map: Option<Rc<SourceMap>>) -> SarifEmitter {
+        SarifEmitter::new(Box::new(io::stdout()), source_map)
+    }
+
+    /// Sets the tool name and version reported in the log, they are `kona`
+    /// and the version of this crate by default.
+    pub fn with_tool(mut self, name: impl Into<String>, version: impl Into<String>) -> SarifEmitter {
+        self.tool_name = name.into();
+        self.tool_version = version.into();
+        self
+    }
+
+    /// Sets the directory that file URIs are relative to, see `%SRCROOT%`.
+    pub fn with_source_root(mut self, root: impl Into<PathBuf>) -> SarifEmitter {
+        self.source_root = Some(canonicalize(root.into()));
+        self
+    }
+}
+
+impl Emitter for SarifEmitter {
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()> {
+        let result = SarifResult::from_diagnostic(diag, self.source_map.as_deref(),
+            self.source_root.as_deref());
+        self.results.push(result);
+        Ok(())
+    }
+
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        self.source_map.clone()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        let log = SarifLog {
+            schema: SARIF_SCHEMA,
+            version: SARIF_VERSION,
+            runs: vec![SarifRun {
+                tool: SarifTool {
+                    driver: SarifDriver {
+                        name: &self.tool_name,
+                        version: &self.tool_version,
+                    },
+                },
+                original_uri_base_ids: self.source_root
+                    .as_deref()
+                    .map(|root| {
+                        // A base URI must end with a slash.
+                        let uri = format!("{}/", file_uri(root).trim_end_matches('/'));
+                        let location = SarifArtifactLocation { uri, uri_base_id: None };
+                        BTreeMap::from([(SOURCE_ROOT, location)])
+                    })
+                    .unwrap_or_default(),
+                column_kind: "unicodeCodePoints",
+                results: std::mem::take(&mut self.results),
+            }],
+        };
+        serde_json::to_writer_pretty(&mut self.out, &log)?;
+        writeln!(self.out)?;
+        self.out.flush()
+    }
+}
+
+#[derive(Serialize)]
+struct SarifLog<'a> {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRun<'a> {
+    tool: SarifTool<'a>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    original_uri_base_ids: BTreeMap<&'static str, SarifArtifactLocation>,
+
+    column_kind: &'static str,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct SarifTool<'a> {
+    driver: SarifDriver<'a>,
+}
+
+#[derive(Serialize)]
+struct SarifDriver<'a> {
+    name: &'a str,
+    version: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule_id: Option<String>,
+
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_locations: Vec<SarifLocation>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fixes: Vec<SarifFix>,
+}
+
+#[derive(Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<usize>,
+    physical_location: SarifPhysicalLocation,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<SarifMessage>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    region: SarifRegion,
+}
+
+#[derive(Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct SarifArtifactLocation {
+    uri: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri_base_id: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+    byte_offset: u32,
+    byte_length: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifFix {
+    description: SarifMessage,
+    artifact_changes: Vec<SarifArtifactChange>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifArtifactChange {
+    artifact_location: SarifArtifactLocation,
+    replacements: Vec<SarifReplacement>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifReplacement {
+    deleted_region: SarifRegion,
+    inserted_content: SarifInsertedContent,
+}
+
+#[derive(Serialize)]
+struct SarifInsertedContent {
+    text: String,
+}
+
+impl SarifResult {
+    fn from_diagnostic(
+        diag: &Diagnostic,
+        source_map: Option<&SourceMap>,
+        root: Option<&Path>,
+    ) -> SarifResult {
+        let mut text = diag.message.clone();
+        let mut related_locations = vec![];
+
+        let primary = &diag.labels.primary_label;
+        let locations = SarifPhysicalLocation::from_span(primary.span, source_map, root)
+            .map(|physical_location| SarifLocation {
+                id: None,
+                physical_location,
+                message: SarifMessage::non_empty(&primary.message),
+            })
+            .into_iter()
+            .collect();
+
+        for label in &diag.labels.sublabels {
+            let physical_location = SarifPhysicalLocation::from_span(label.span, source_map, root);
+            if let Some(physical_location) = physical_location {
+                related_locations.push(SarifLocation {
+                    id: Some(related_locations.len()),
+                    physical_location,
+                    message: SarifMessage::non_empty(&label.message),
+                });
+            }
+        }
+
+        for child in &diag.children {
+            let child_text = format!("{}: {}", child.level.as_str(), child.message);
+            match SarifPhysicalLocation::from_span(child.span(), source_map, root) {
+                Some(physical_location) => related_locations.push(SarifLocation {
+                    id: Some(related_locations.len()),
+                    physical_location,
+                    message: Some(SarifMessage { text: child_text }),
+                }),
+                None => {
+                    text.push('\n');
+                    text.push_str(&child_text);
+                }
+            }
+        }
+
+        SarifResult {
+            rule_id: diag.code.clone(),
+            level: sarif_level(diag.level),
+            message: SarifMessage { text },
+            locations,
+            related_locations,
+            fixes: diag.suggestions
+                .iter()
+                .filter_map(|suggestion| SarifFix::from_suggestion(suggestion, source_map, root))
+                .collect(),
+        }
+    }
+}
+
+impl SarifMessage {
+    fn non_empty(text: &str) -> Option<SarifMessage> {
+        (!text.is_empty()).then(|| SarifMessage { text: text.to_string() })
+    }
+}
+
+impl SarifPhysicalLocation {
+    fn from_span(
+        span: Span,
+        source_map: Option<&SourceMap>,
+        root: Option<&Path>,
+    ) -> Option<SarifPhysicalLocation> {
+        if span.is_dummy() {
+            return None;
+        }
+        let (artifact_location, region) = SarifRegion::from_span(span, source_map?, root)?;
+        Some(SarifPhysicalLocation { artifact_location, region })
+    }
+}
+
+impl SarifRegion {
+    /// Resolves the span to its file location and region, returns `None` if
+    /// the span is not in the source map.
+    fn from_span(
+        span: Span,
+        source_map: &SourceMap,
+        root: Option<&Path>,
+    ) -> Option<(SarifArtifactLocation, SarifRegion)> {
+        let file = source_map.lookup_file_at_span(span).ok()?;
+        let start = source_map.lookup_start_pos_info(span).ok()?;
+        let end = source_map.lookup_end_pos_info(span).ok()?;
+
+        let region = SarifRegion {
+            start_line: start.line(),
+            start_column: start.col() + 1,
+            end_line: end.line(),
+            end_column: end.col() + 1,
+            byte_offset: span.start().to_u32() - file.start_pos().to_u32(),
+            byte_length: span.end().to_u32() - span.start().to_u32(),
+        };
+        Some((SarifArtifactLocation::from_file(&file, root), region))
+    }
+}
+
+impl SarifArtifactLocation {
+    /// Returns the location of a local file relative to the source root if
+    /// it's under it, otherwise its absolute URI. Files that are not local,
+    /// e.g. test files, are always relative.
+    fn from_file(file: &SourceFile, root: Option<&Path>) -> SarifArtifactLocation {
+        let relative = |path: &str| SarifArtifactLocation {
+            uri: encode_uri_path(&path.replace('\\', "/"), false),
+            uri_base_id: Some(SOURCE_ROOT),
+        };
+        match file.local_path() {
+            Some(path) => match root.and_then(|root| path.strip_prefix(root).ok()) {
+                Some(path) => relative(&path.to_string_lossy()),
+                None => SarifArtifactLocation { uri: file_uri(path), uri_base_id: None },
+            },
+            None => relative(&file.name()),
+        }
+    }
+}
+
+impl SarifFix {
+    /// Converts a suggestion to a fix, returns `None` if any of its
+    /// substitutions cannot be resolved.
+    fn from_suggestion(
+        suggestion: &CodeSuggestion,
+        source_map: Option<&SourceMap>,
+        root: Option<&Path>,
+    ) -> Option<SarifFix> {
+        let source_map = source_map?;
+        let mut artifact_changes: Vec<SarifArtifactChange> = vec![];
+
+        for sub in &suggestion.substitutions {
+            let (artifact_location, deleted_region) =
+                SarifRegion::from_span(sub.span, source_map, root)?;
+            let replacement = SarifReplacement {
+                deleted_region,
+                inserted_content: SarifInsertedContent { text: sub.replacement.clone() },
+            };
+            let change = artifact_changes
+                .iter_mut()
+                .find(|change| change.artifact_location == artifact_location);
+            match change {
+                Some(change) => change.replacements.push(replacement),
+                None => artifact_changes.push(SarifArtifactChange {
+                    artifact_location,
+                    replacements: vec![replacement],
+                }),
+            }
+        }
+
+        Some(SarifFix {
+            description: SarifMessage { text: suggestion.message.clone() },
+            artifact_changes,
+        })
+    }
+}
+
+/// Returns the absolute `file://` URI of a path.
+fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    // Windows paths are canonicalized with a `//?/` prefix, e.g. `//?/C:/src`,
+    // their URIs look like `file:///C:/src`.
+    let path = path.strip_prefix("//?/").unwrap_or(&path);
+    let slash = if path.starts_with('/') { "" } else { "/" };
+    format!("file://{}{}", slash, encode_uri_path(path, true))
+}
+
+/// Percent-encodes a `/`-separated path for a URI. Colons are kept in
+/// absolute paths for Windows drives, but are encoded in relative paths, where
+/// they would be taken for a scheme.
+fn encode_uri_path(path: &str, absolute: bool) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            b':' if absolute => encoded.push(':'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Canonicalizes a source root, so that it's a prefix of the canonical paths
+/// of local files.
+fn canonicalize(path: PathBuf) -> PathBuf {
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+fn sarif_level(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warn => "warning",
+        Level::Note | Level::Help => "note",
+    }
+}
+
+#[cfg(test)]
+mod sarif_emitter_tests {
+    use std::rc::Rc;
+
+    use crate::source::SourceMap;
+    use crate::diagnostic::{DiagnosticEngine, Applicability};
+
+    use super::SarifEmitter;
+    use super::super::test_utils::SharedBuffer;
+
+    #[test]
+    fn test_sarif_emitter() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(
+            Some("test.sml".to_string()),
+            "fun f n =\n  case n in _ => 0\n".to_string(),
+        );
+        let start = file.start_pos().to_u32();
+
+        let buffer = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(
+            Box::new(SarifEmitter::new(Box::new(buffer.clone()), Some(source_map))),
+        );
+        engine.create_err("unexpected token")
+            .set_code("E0042")
+            .set_primary_label(start + 19..start + 21, "expect keyword `of`")
+            .add_sublabel(start + 12..start + 16, "case-of expression starts here")
+            .add_note("a note")
+            .add_suggestion(start + 19..start + 21, "replace `in` with `of`", "of",
+                Applicability::MachineApplicable)
+            .emit();
+        engine.create_warn("unused variable").emit();
+
+        assert!(buffer.contents().is_empty());
+        engine.finish();
+
+        let log: serde_json::Value = serde_json::from_str(&buffer.contents()).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(log["runs"].as_array().unwrap().len(), 1);
+
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "kona");
+        assert_eq!(run["results"].as_array().unwrap().len(), 2);
+
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "E0042");
+        assert_eq!(result["level"], "error");
+        assert_eq!(result["message"]["text"], "unexpected token\nnote: a note");
+
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "test.sml");
+        assert_eq!(location["artifactLocation"]["uriBaseId"], "%SRCROOT%");
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(location["region"]["startColumn"], 10);
+        assert_eq!(location["region"]["endColumn"], 12);
+        assert_eq!(location["region"]["byteOffset"], 19);
+        assert_eq!(location["region"]["byteLength"], 2);
+        assert_eq!(result["locations"][0]["message"]["text"], "expect keyword `of`");
+
+        let related = &result["relatedLocations"][0];
+        assert_eq!(related["id"], 0);
+        assert_eq!(related["physicalLocation"]["region"]["startColumn"], 3);
+        assert_eq!(related["message"]["text"], "case-of expression starts here");
+
+        let fix = &result["fixes"][0];
+        assert_eq!(fix["description"]["text"], "replace `in` with `of`");
+        let replacement = &fix["artifactChanges"][0]["replacements"][0];
+        assert_eq!(replacement["deletedRegion"]["byteOffset"], 19);
+        assert_eq!(replacement["insertedContent"]["text"], "of");
+
+        let warning = &run["results"][1];
+        assert_eq!(warning["level"], "warning");
+        assert_eq!(warning["locations"].as_array().unwrap().len(), 0);
+        assert!(warning.get("relatedLocations").is_none());
+        assert!(warning.get("ruleId").is_none());
+    }
+
+    #[test]
+    fn test_sarif_uris() {
+        let dir = std::env::temp_dir().join(format!("kona-sarif-{}", std::process::id()));
+        let root = dir.join("src root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("my file.sml"), "val x = 1\n").unwrap();
+        std::fs::write(dir.join("other.sml"), "val y = 2\n").unwrap();
+
+        let source_map = Rc::new(SourceMap::new());
+        let inside = source_map.load_local_file(root.join("my file.sml")).unwrap();
+        let outside = source_map.load_local_file(dir.join("other.sml")).unwrap();
+        let (inside, outside) = (inside.start_pos().to_u32(), outside.start_pos().to_u32());
+
+        let buffer = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            SarifEmitter::new(Box::new(buffer.clone()), Some(source_map)).with_source_root(&root),
+        ));
+        engine.create_warn("unused")
+            .set_primary_label(inside + 4..inside + 5, "")
+            .add_sublabel(outside + 4..outside + 5, "")
+            .emit();
+        engine.finish();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let log: serde_json::Value = serde_json::from_str(&buffer.contents()).unwrap();
+        let run = &log["runs"][0];
+        let base = run["originalUriBaseIds"]["%SRCROOT%"]["uri"].as_str().unwrap();
+        assert!(base.starts_with("file:///") && base.ends_with("/src%20root/"));
+
+        let result = &run["results"][0];
+        let location = &result["locations"][0]["physicalLocation"]["artifactLocation"];
+        assert_eq!(location["uri"], "my%20file.sml");
+        assert_eq!(location["uriBaseId"], "%SRCROOT%");
+
+        let location = &result["relatedLocations"][0]["physicalLocation"]["artifactLocation"];
+        let uri = location["uri"].as_str().unwrap();
+        assert!(uri.starts_with("file:///") && uri.ends_with("/other.sml"));
+        assert!(location.get("uriBaseId").is_none());
+    }
+
+    #[test]
+    fn test_span_at_end_of_file() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("test.sml".to_string()), "val x =\n".to_string());
+        source_map.load_test_file(Some("other.sml".to_string()), "val y = 2\n".to_string());
+        let end = file.end_pos().to_u32();
+
+        let buffer = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(
+            Box::new(SarifEmitter::new(Box::new(buffer.clone()), Some(source_map))),
+        );
+        engine.create_err("unexpected end of file").set_primary_label(end..end, "").emit();
+        engine.finish();
+
+        let log: serde_json::Value = serde_json::from_str(&buffer.contents()).unwrap();
+        let location = &log["runs"][0]["results"][0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "test.sml");
+        assert_eq!(location["region"]["startLine"], 1);
+        assert_eq!(location["region"]["startColumn"], 9);
+        assert_eq!(location["region"]["byteOffset"], 8);
+        assert_eq!(location["region"]["byteLength"], 0);
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{rc::Rc, io::{self, Write}};
+
+use crate::source::{SourceMap, Span};
+
+use crate::diagnostic::{Diagnostic, Level};
+
+use super::Emitter;
+
+/// Writes each diagnostic as a single line, for editors and `grep`:
+///
+/// ```text
+/// example.sml:5:11: error[E0042]: unexpected token keyword `in` in case-of expression
+/// example.sml:3:5: note: the case-of expression starts here
+/// ```
+///
+/// The first line is at the primary label, followed by one `note` line per
+/// sublabel, only `file:line:col: note` if it has no message. Lines and
+/// columns are 1-based, columns are counted in Unicode code points. This is
+/// the format the compilation modes of Vim (`errorformat`) and Emacs parse by
+/// default. Labels that cannot be resolved in the source map are printed
+/// without a location, or not at all if they have no message either. Children
+/// and suggestions are not printed. The lines of multi-line messages are
+/// joined with `; `.
+pub struct ShortEmitter {
+    out: Box<dyn Write>,
+    source_map: Option<Rc<SourceMap>>,
+}
+
+impl ShortEmitter {
+    pub fn new(out: Box<dyn Write>, source_map: Option<Rc<SourceMap>>) -> ShortEmitter {
+        ShortEmitter { out, source_map }
+    }
+
+    pub fn stderr(source_map: Option<Rc<SourceMap>>) -> ShortEmitter {
+        ShortEmitter::new(Box::new(io::stderr()), source_map)
+    }
+
+    /// Returns the `file:line:col: ` prefix of a span, or an empty string if
+    /// it cannot be resolved.
+    fn location(&self, span: Span) -> String {
+        if span.is_dummy() {
+            return String::new();
+        }
+        self.source_map
+            .as_ref()
+            .and_then(|source_map| source_map.lookup_start_pos_info(span).ok())
+            .map_or_else(String::new, |pos| {
+                format!("{}:{}:{}: ", pos.name(), pos.line(), pos.col() + 1)
+            })
+    }
+}
+
+impl Emitter for ShortEmitter {
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()> {
+        let code = diag.code.as_ref().map_or_else(String::new, |code| format!("[{}]", code));
+        let location = self.location(diag.labels.primary_label.span);
+        writeln!(self.out, "{}{}{}: {}",
+            location, diag.level.as_str(), code, one_line(&diag.message))?;
+
+        for label in &diag.labels.sublabels {
+            let location = self.location(label.span);
+            if label.message.is_empty() {
+                if !location.is_empty() {
+                    writeln!(self.out, "{}{}", location, Level::Note.as_str())?;
+                }
+            } else {
+                writeln!(self.out, "{}{}: {}",
+                    location, Level::Note.as_str(), one_line(&label.message))?;
+            }
+        }
+        self.out.flush()
+    }
+
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        self.source_map.clone()
+    }
+}
+
+/// Joins the lines of a message, so that each diagnostic stays on one line.
+fn one_line(message: &str) -> String {
+    message
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod short_emitter_tests {
+    use std::rc::Rc;
+
+    use crate::source::SourceMap;
+    use crate::diagnostic::DiagnosticEngine;
+
+    use super::ShortEmitter;
+    use super::super::test_utils::SharedBuffer;
+
+    #[test]
+    fn test_short_format() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("test.sml".to_string()),
+            "val x = 1\nval y = x + \"a\"\n".to_string());
+        let start = file.start_pos().to_u32();
+
+        let out = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            ShortEmitter::new(Box::new(out.clone()), Some(source_map)),
+        ));
+        engine.create_err("mismatched types")
+            .set_code("E0001")
+            .set_primary_label(start + 22..start + 25, "expected `int`")
+            .add_sublabel(start + 4..start + 5, "`x` is an `int`")
+            .add_sublabel(start + 18..start + 19, "")
+            .emit();
+        engine.create_warn("no location").emit();
+
+        assert_eq!(out.contents(), "\
+test.sml:2:13: error[E0001]: mismatched types
+test.sml:1:5: note: `x` is an `int`
+test.sml:2:9: note
+warning: no location
+");
+    }
+
+    #[test]
+    fn test_multi_line_messages() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("test.sml".to_string()), "val x = y\n".to_string());
+        let start = file.start_pos().to_u32();
+
+        let out = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            ShortEmitter::new(Box::new(out.clone()), Some(source_map)),
+        ));
+        engine.create_err("mismatched types\n  expected `int`\r\n\n  found `bool`\n")
+            .set_primary_label(start + 8..start + 9, "")
+            .add_sublabel(start + 4..start + 5, "declared here\nas an `int`")
+            .emit();
+
+        assert_eq!(out.contents(), "\
+test.sml:1:9: error: mismatched types; expected `int`; found `bool`
+test.sml:1:5: note: declared here; as an `int`
+");
+    }
+
+    #[test]
+    fn test_span_at_end_of_file() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("test.sml".to_string()), "val x =\n".to_string());
+        source_map.load_test_file(Some("other.sml".to_string()), "val y = 2\n".to_string());
+        let end = file.end_pos().to_u32();
+
+        let out = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            ShortEmitter::new(Box::new(out.clone()), Some(source_map)),
+        ));
+        engine.create_err("unexpected end of file").set_primary_label(end..end, "").emit();
+
+        assert_eq!(out.contents(), "test.sml:1:9: error: unexpected end of file\n");
+    }
+}
@@ -0,0 +1,1058 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+//! The layout engine for source snippets.
+//!
+//! A snippet is a range of source lines with some labels drawn under them.
+//! The layout looks like the one of `rustc`:
+//!
+//! ```text
+//!   |
+//! 1 | / fun fact n =
+//! 2 | |   if n = 0 then 1 else n * fact (n - 1)
+//!   | |        -               ^^^^^^^^^^^^^^^^ expected `bool`
+//!   | |        |
+//!   | |        because of this
+//! 3 | |   | _ => 0
+//!   | |_____- in this function
+//! ```
+//!
+//! - Labels on the same line are drawn side by side. The message of the
+//!   rightmost label is printed right after its marks, the others are pushed
+//!   down and connected to their marks with `|`;
+//! - Multi-line labels are drawn in the gutter between the line numbers and the
+//!   source code. A label starting at the beginning of a line is opened by `/`,
+//!   otherwise by `_____^` under its first line. It is closed by `|____^` under
+//!   its last line, followed by the message.
+//! - All labels in the same file are drawn in one snippet. Lines without
+//!   labels between them are elided with `...`, so are the middle lines of
+//!   long multi-line labels, see [`RenderConfig`].
+
+use std::{rc::Rc, borrow::Cow, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::source::{
+    SourceMap, SourceFile, SourceLine, PosInfo, LookupError, grapheme_width, is_invisible_char,
+    escape_invisible_char,
+};
+
+use crate::diagnostic::{DiagnosticLabel, DiagnosticLabels, Level};
+
+use super::styled_buffer::{StyledBuffer, Style};
+use super::RenderConfig;
+
+/// A group of labels drawn in one source snippet.
+///
+/// Labels in the same file are merged into the same snippet, so that each file
+/// is printed once with all its labels. Labels in other files get a snippet of
+/// their own.
+pub(crate) struct Snippet<'a> {
+    file: Rc<SourceFile>,
+
+    /// The distance between two tab stops, the display columns of the labels
+    /// are calculated with it.
+    tab_width: usize,
+
+    /// The labels in this snippet, the primary label (if any) comes first.
+    labels: Vec<SnippetLabel<'a>>,
+}
+
+/// A label that cannot be resolved in the source map, printed as a single
+/// line instead of a snippet.
+pub(crate) struct UnresolvedLabel<'a> {
+    pub(crate) label: &'a DiagnosticLabel,
+    pub(crate) is_primary: bool,
+
+    /// Why it cannot be resolved, e.g. "across source files".
+    pub(crate) reason: &'static str,
+}
+
+/// A label resolved against the source map.
+struct SnippetLabel<'a> {
+    label: &'a DiagnosticLabel,
+    is_primary: bool,
+    start: PosInfo,
+
+    /// The exclusive display column of the end of this label, on its last
+    /// line.
+    end_col: usize,
+
+    first_line: u32,
+    last_line: u32,
+}
+
+impl<'a> SnippetLabel<'a> {
+    fn resolve(
+        source_map: &SourceMap,
+        label: &'a DiagnosticLabel,
+        is_primary: bool,
+        tab_width: usize,
+    ) -> Result<(Rc<SourceFile>, SnippetLabel<'a>), LookupError> {
+        let span = label.span;
+        let file = source_map.lookup_file_at_span(span)?;
+        let start = source_map.lookup_start_pos_info_with_tab_width(span, tab_width)?;
+
+        // The end position is exclusive, it may be located at the beginning of
+        // the next line, or right after the last character of the file.
+        let last = if span.end() > span.start() {
+            source_map.lookup_pos_info_with_tab_width(span.end() - 1u32, tab_width)?
+        } else {
+            start.clone()
+        };
+        let end_col = match source_map.lookup_pos_info_with_tab_width(span.end(), tab_width) {
+            Ok(end) if end.line() == last.line() => end.col_display(),
+            _ => last.col_display() + 1,
+        };
+
+        // A zero-length span is on the line of its start.
+        let (first_line, last_line) = (start.line() as u32 - 1, last.line() as u32 - 1);
+        let label = SnippetLabel { label, is_primary, start, end_col, first_line, last_line };
+        Ok((file, label))
+    }
+
+    fn start_col(&self) -> usize {
+        self.start.col_display()
+    }
+
+    /// Returns the number of marks under this single-line label.
+    fn mark_len(&self) -> usize {
+        self.end_col.saturating_sub(self.start_col()).max(1)
+    }
+
+    fn is_multiline(&self) -> bool {
+        self.first_line != self.last_line
+    }
+
+    fn has_message(&self) -> bool {
+        !self.label.message.is_empty()
+    }
+
+    fn style(&self, level: Level) -> Style {
+        if self.is_primary {
+            Style::Primary(level)
+        } else {
+            Style::Secondary
+        }
+    }
+
+    fn mark_char(&self, level: Level) -> char {
+        match (self.is_primary, level) {
+            (true, Level::Note | Level::Help) => '-',
+            (true, _) => '^',
+            (false, _) => '-',
+        }
+    }
+}
+
+/// A multi-line label and the gutter column it is drawn in.
+struct MultilineLabel {
+    /// Index into `Snippet::labels`.
+    label: usize,
+
+    /// 1-based depth in the gutter, outer labels have smaller depths.
+    depth: usize,
+
+    /// Whether this label starts at the beginning of its first line, in which
+    /// case it is opened by a `/` in the gutter.
+    opens_with_slash: bool,
+}
+
+impl<'a> Snippet<'a> {
+    /// Resolves all labels and groups them into snippets, one per file.
+    /// Labels that cannot be resolved, e.g. without a source map, are
+    /// returned separately. Labels with dummy spans are skipped.
+    pub(crate) fn group_labels(
+        source_map: Option<&SourceMap>,
+        labels: &'a DiagnosticLabels,
+        tab_width: usize,
+    ) -> (Vec<Snippet<'a>>, Vec<UnresolvedLabel<'a>>) {
+        let labels = std::iter::once((&labels.primary_label, true))
+            .chain(labels.sublabels.iter().map(|label| (label, false)))
+            .filter(|(label, _)| !label.span.is_dummy());
+
+        let mut snippets: Vec<Snippet<'a>> = vec![];
+        let mut unresolved = vec![];
+        for (label, is_primary) in labels {
+            let resolved = match source_map {
+                Some(source_map) => SnippetLabel::resolve(source_map, label, is_primary, tab_width)
+                    .map_err(|err| match err {
+                        LookupError::SpanAcrossFiles => "across source files",
+                        _ => "out of the source map",
+                    }),
+                None => Err("no source map"),
+            };
+            let (file, label) = match resolved {
+                Ok(resolved) => resolved,
+                Err(reason) => {
+                    unresolved.push(UnresolvedLabel { label, is_primary, reason });
+                    continue;
+                }
+            };
+
+            let mut snippet = Snippet {
+                file,
+                tab_width,
+                labels: vec![label],
+            };
+
+            // Merges the new label into the snippet of its file. Snippets keep
+            // the order in which their files first appear, so that the snippet
+            // with the primary label always stays in front.
+            match snippets.iter_mut().find(|other| other.file.is_same_file(&snippet.file)) {
+                Some(other) => other.labels.append(&mut snippet.labels),
+                None => snippets.push(snippet),
+            }
+        }
+        (snippets, unresolved)
+    }
+
+    /// Returns the 0-based indices of the lines to draw: the lines covered by
+    /// labels and their context lines, and the single lines between them,
+    /// which take no more room than a `...`. Only the first and last lines of
+    /// long multi-line labels are drawn.
+    fn lines(&self, config: &RenderConfig) -> Vec<u32> {
+        let mut covered = self.labels
+            .iter()
+            .flat_map(|label| {
+                let (first, last) = (label.first_line, label.last_line);
+                let edge = config.elided_edge_lines as u32;
+                let num_lines = (last - first + 1) as usize;
+                if num_lines > config.max_multiline_lines && num_lines > 2 * edge as usize {
+                    // Lines kept at both ends, the first and last lines are
+                    // always kept for the openings and closings.
+                    let edge = edge.max(1);
+                    (first..first + edge).chain(last + 1 - edge..=last).collect::<Vec<_>>()
+                } else {
+                    (first..=last).collect()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // The context lines before and after each label. The empty line after
+        // the last newline of the file is not worth showing.
+        let mut num_lines = self.file.count_lines() as u32;
+        if num_lines > 1 && self.file.lookup_line_source(num_lines as usize - 1).is_empty() {
+            num_lines -= 1;
+        }
+        let context = config.context_lines as u32;
+        for label in self.labels.iter() {
+            covered.extend(label.first_line.saturating_sub(context)..label.first_line);
+            covered.extend(label.last_line + 1..(label.last_line + 1 + context).min(num_lines));
+        }
+        covered.sort_unstable();
+        covered.dedup();
+
+        let mut lines: Vec<u32> = vec![];
+        for line_idx in covered {
+            if let Some(&last) = lines.last() {
+                if line_idx == last + 2 {
+                    lines.push(last + 1);
+                }
+            }
+            lines.push(line_idx);
+        }
+        lines
+    }
+
+    /// Returns the location of the first label in this snippet, which is the
+    /// primary label if this snippet contains it.
+    pub(crate) fn location(&self) -> &PosInfo {
+        &self.labels[0].start
+    }
+
+    /// Returns the largest 1-based line number in this snippet.
+    pub(crate) fn max_line_number(&self, config: &RenderConfig) -> u32 {
+        self.lines(config).last().map_or(0, |&line_idx| line_idx + 1)
+    }
+
+    /// Returns `true` if any label covers the line, other lines are only
+    /// shown as context.
+    fn is_labeled(&self, line_idx: u32) -> bool {
+        self.labels
+            .iter()
+            .any(|label| label.first_line <= line_idx && line_idx <= label.last_line)
+    }
+
+    /// Assigns gutter depths to the multi-line labels. Labels whose lines
+    /// overlap get different depths, the one starting first is the outer one.
+    fn layout_multiline_labels(&self) -> Vec<MultilineLabel> {
+        let mut indices = (0..self.labels.len())
+            .filter(|&idx| self.labels[idx].is_multiline())
+            .collect::<Vec<_>>();
+        indices.sort_by_key(|&idx| {
+            let label = &self.labels[idx];
+            (label.first_line, label.start_col())
+        });
+
+        let mut multilines: Vec<MultilineLabel> = vec![];
+        for idx in indices {
+            let label = &self.labels[idx];
+            let depth = (1..)
+                .find(|&depth| !multilines.iter().any(|ml| {
+                    let other = &self.labels[ml.label];
+                    ml.depth == depth
+                        && other.first_line <= label.last_line
+                        && label.first_line <= other.last_line
+                }))
+                .unwrap();
+
+            let line_src = self.file.lookup_line_source(label.first_line as usize);
+            let indentation = line_src.len() - line_src.trim_start().len();
+            let indentation = display_width(&line_src[..indentation], self.tab_width);
+
+            multilines.push(MultilineLabel {
+                label: idx,
+                depth,
+                opens_with_slash: label.start_col() <= indentation,
+            });
+        }
+        multilines
+    }
+
+    /// Draws the source lines and labels of this snippet at the end of the
+    /// buffer. `indent` is the width of the line number column.
+    pub(crate) fn render(
+        &self,
+        buffer: &mut StyledBuffer,
+        indent: usize,
+        level: Level,
+        config: &RenderConfig,
+    ) {
+        let multilines = self.layout_multiline_labels();
+        let max_depth = multilines.iter().map(|ml| ml.depth).max().unwrap_or(0);
+        let code_col = indent + 3 + 2 * max_depth;
+        let lines = self.lines(config);
+        let lines_width = self.lines_width(&lines);
+        let width = config.max_width
+            .map(|max_width| max_width.saturating_sub(code_col).max(MIN_WINDOW_WIDTH))
+            .filter(|&width| lines_width > width);
+        let layout = LineLayout {
+            indent,
+            code_col,
+            left: width.map_or(0, |width| self.window_start(lines_width, width)),
+            width,
+            level,
+        };
+
+        let mut prev_line = None;
+        for line_idx in lines {
+            if let Some(prev) = prev_line.filter(|&prev| line_idx > prev + 1) {
+                // Multi-line labels going through the elided lines keep their
+                // lines in the gutter.
+                let row = buffer.num_lines();
+                buffer.puts(row, 0, "...", Style::LineNumber);
+                for ml in multilines.iter() {
+                    let label = &self.labels[ml.label];
+                    if label.first_line <= prev && line_idx <= label.last_line {
+                        buffer.putc(row, indent + 3 + 2 * (ml.depth - 1), '|',
+                            label.style(level));
+                    }
+                }
+            }
+            self.render_line(buffer, &layout, &multilines, line_idx);
+            prev_line = Some(line_idx);
+        }
+    }
+
+    /// Returns the display width of the widest line.
+    fn lines_width(&self, lines: &[u32]) -> usize {
+        lines
+            .iter()
+            .map(|&line_idx| {
+                let line_src = self.file.lookup_line_source(line_idx as usize);
+                display_width(line_src.trim_end_matches(['\n', '\r']), self.tab_width)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the first display column to draw when the widest line, which is
+    /// `line_width` wide, doesn't fit in `width`. The window is centered on
+    /// the labels, or on the primary label if they don't fit together, but
+    /// never starts after the first of them or leaves room after the end of
+    /// the lines.
+    fn window_start(&self, line_width: usize, width: usize) -> usize {
+        // Multi-line labels are marked at their start on the first line and at
+        // their end on the last line.
+        let range = |labels: &[&SnippetLabel]| {
+            let start = labels
+                .iter()
+                .map(|label| label.start_col().min(label.end_col.saturating_sub(1)))
+                .min()
+                .unwrap_or(0);
+            let end = labels
+                .iter()
+                .map(|label| label.end_col.max(label.start_col() + 1))
+                .max()
+                .unwrap_or(0);
+            (start, end)
+        };
+        let labels = self.labels.iter().collect::<Vec<_>>();
+        let (mut labels_start, mut labels_end) = range(&labels);
+        if labels_end - labels_start > width - 2 * ELLIPSIS.len() {
+            // The sublabels are cut, the primary label (or the first label of
+            // a snippet without it) is kept.
+            let focus = labels
+                .iter()
+                .copied()
+                .find(|label| label.is_primary)
+                .or_else(|| labels.iter().copied().min_by_key(|label| label.start_col()));
+            (labels_start, labels_end) = range(focus.as_slice());
+        }
+
+        let center = (labels_start + labels_end) / 2;
+        let left = center
+            .saturating_sub(width / 2)
+            .min(line_width - width)
+            .min(labels_start.saturating_sub(ELLIPSIS.len()));
+        // Cutting fewer columns than the `...` takes is pointless.
+        if left <= ELLIPSIS.len() { 0 } else { left }
+    }
+
+    fn render_line(
+        &self,
+        buffer: &mut StyledBuffer,
+        layout: &LineLayout,
+        multilines: &[MultilineLabel],
+        line_idx: u32,
+    ) {
+        let LineLayout { indent, code_col, level, .. } = *layout;
+        let gutter_col = |depth: usize| indent + 3 + 2 * (depth - 1);
+        let col = |display_col: usize| layout.column(display_col);
+
+        // Draws the source line.
+
+        let line = SourceLine::new(self.file.clone(), line_idx);
+        let row = buffer.num_lines();
+        buffer.puts(row, 0,
+            &format!("{:>indent$} |", line.line_number(), indent = indent),
+            Style::LineNumber);
+        let style = if self.is_labeled(line_idx) { Style::Plain } else { Style::Context };
+        let source = line.source();
+        let source = source.trim_end_matches(['\n', '\r']);
+        match layout.width {
+            Some(width) => {
+                let (source, cut_left, cut_right) =
+                    window_line(source, self.tab_width, layout.left, width);
+                buffer.puts(row, code_col, &source, style);
+                if cut_left {
+                    buffer.puts(row, code_col, ELLIPSIS, Style::LineNumber);
+                }
+                if cut_right {
+                    let len = source.chars().count();
+                    buffer.puts(row, code_col + len - ELLIPSIS.len(), ELLIPSIS,
+                        Style::LineNumber);
+                }
+            }
+            None => buffer.puts(row, code_col, &escape_line(source, self.tab_width), style),
+        }
+
+        for ml in multilines {
+            let label = &self.labels[ml.label];
+            let ch = if label.first_line == line_idx && ml.opens_with_slash {
+                '/'
+            } else if label.first_line < line_idx && line_idx <= label.last_line {
+                '|'
+            } else {
+                continue;
+            };
+            buffer.putc(row, gutter_col(ml.depth), ch, label.style(level));
+        }
+
+        // Lays out the single-line labels, from right to left. Only the
+        // rightmost label can print its message right after its marks, the
+        // other messages are pushed down one by one.
+
+        let mut singles = self.labels
+            .iter()
+            .filter(|label| !label.is_multiline() && label.first_line == line_idx)
+            .collect::<Vec<_>>();
+        singles.sort_by(|a, b| {
+            (b.start_col(), b.end_col).cmp(&(a.start_col(), a.end_col))
+        });
+
+        let mut placements = vec![];
+        let mut depth = 0;
+        for (idx, label) in singles.iter().enumerate() {
+            if !label.has_message() {
+                continue;
+            }
+            if idx == 0 && singles.iter().all(|other| other.end_col <= label.end_col) {
+                placements.push((label, 0));
+            } else {
+                depth += 1;
+                placements.push((label, depth));
+            }
+        }
+
+        let mut next_row = row + 1;
+        if !singles.is_empty() {
+            next_row += if depth > 0 { depth + 2 } else { 1 };
+        }
+
+        // Then the openings and closings of multi-line labels, each of them
+        // takes a row.
+
+        let mut starts = multilines
+            .iter()
+            .filter(|ml| {
+                let label = &self.labels[ml.label];
+                label.first_line == line_idx && !ml.opens_with_slash
+            })
+            .collect::<Vec<_>>();
+        starts.sort_by_key(|ml| ml.depth);
+        let starts = starts
+            .into_iter()
+            .map(|ml| {
+                next_row += 1;
+                (ml, next_row - 1)
+            })
+            .collect::<Vec<_>>();
+
+        let mut ends = multilines
+            .iter()
+            .filter(|ml| self.labels[ml.label].last_line == line_idx)
+            .collect::<Vec<_>>();
+        ends.sort_by_key(|ml| std::cmp::Reverse(ml.depth));
+        let ends = ends
+            .into_iter()
+            .map(|ml| {
+                next_row += 1;
+                (ml, next_row - 1)
+            })
+            .collect::<Vec<_>>();
+
+        // All rows are laid out, draws the gutters first.
+
+        for r in row + 1..next_row {
+            buffer.puts(r, 0, &format!("{:indent$} |", "", indent = indent),
+                Style::LineNumber);
+        }
+
+        for ml in multilines {
+            let label = &self.labels[ml.label];
+            if line_idx < label.first_line || label.last_line < line_idx {
+                continue;
+            }
+
+            let from = match starts.iter().find(|(start, _)| start.label == ml.label) {
+                Some((_, start_row)) => start_row + 1,
+                None => row + 1,
+            };
+            let to = match ends.iter().find(|(end, _)| end.label == ml.label) {
+                Some((_, end_row)) => *end_row,
+                None => next_row,
+            };
+            for r in from..to {
+                buffer.putc(r, gutter_col(ml.depth), '|', label.style(level));
+            }
+        }
+
+        // Draws the marks of single-line labels, the primary label is drawn
+        // last, so that it is on the top when labels overlap.
+
+        let marks = singles
+            .iter()
+            .filter(|label| !label.is_primary)
+            .chain(singles.iter().filter(|label| label.is_primary));
+        for label in marks {
+            let start = label.start_col();
+            if let Some(cols) = layout.columns(start, start + label.mark_len()) {
+                let marks = label.mark_char(level).to_string().repeat(cols.len());
+                buffer.puts(row + 1, cols.start, &marks, label.style(level));
+            }
+        }
+
+        for &(label, depth) in placements.iter() {
+            let style = label.style(level);
+            let col = col(label.start_col());
+            if depth == 0 {
+                let last_mark = label.start_col() + label.mark_len() - 1;
+                buffer.puts(row + 1, layout.column(last_mark) + 2,
+                    &label.label.message, style);
+            } else {
+                for r in row + 2..row + 2 + depth {
+                    buffer.putc(r, col, '|', style);
+                }
+            }
+        }
+        for &(label, depth) in placements.iter() {
+            if depth > 0 {
+                buffer.puts(row + 2 + depth, col(label.start_col()), &label.label.message,
+                    label.style(level));
+            }
+        }
+
+        // Draws the openings and closings of multi-line labels.
+
+        for &(ml, r) in starts.iter() {
+            let label = &self.labels[ml.label];
+            let style = label.style(level);
+            let col = col(label.start_col());
+            for c in gutter_col(ml.depth) + 1..col {
+                buffer.putc(r, c, '_', style);
+            }
+            buffer.putc(r, col, label.mark_char(level), style);
+        }
+
+        for &(ml, r) in ends.iter() {
+            let label = &self.labels[ml.label];
+            let style = label.style(level);
+            let col = col(label.end_col.saturating_sub(1));
+            buffer.putc(r, gutter_col(ml.depth), '|', style);
+            for c in gutter_col(ml.depth) + 1..col {
+                buffer.putc(r, c, '_', style);
+            }
+            buffer.putc(r, col, label.mark_char(level), style);
+            if label.has_message() {
+                buffer.puts(r, col + 2, &label.label.message, style);
+            }
+        }
+    }
+}
+
+/// Returns the width of the string when displayed from the start of a line,
+/// using the same rules as `SourceFile` when calculating display columns.
+pub(super) fn display_width(s: &str, tab_width: usize) -> usize {
+    display_pieces(s, tab_width).map(|(_, width)| width).sum()
+}
+
+/// Returns the line as it's displayed: tabs are replaced with spaces up to
+/// the next tab stop, so that the marks under the line stay aligned whatever
+/// the terminal's tab stops are, and invisible characters are escaped, so that
+/// bidirectional overrides can't reorder the printed line.
+pub(super) fn escape_line(line: &str, tab_width: usize) -> String {
+    display_pieces(line, tab_width).map(|(piece, _)| piece).collect()
+}
+
+/// Returns the part of the line displayed in the columns `[left, left +
+/// width)`, escaped as by [`escape_line`], and whether it's cut on the left
+/// and on the right. The cut sides are replaced by `...`, other columns stay
+/// where they are relative to `left`.
+fn window_line(line: &str, tab_width: usize, left: usize, width: usize) -> (String, bool, bool) {
+    let line_width = display_width(line, tab_width);
+    let cut_left = left > 0;
+    let cut_right = line_width > left + width;
+    let start = if cut_left { left + ELLIPSIS.len() } else { left };
+    let end = if cut_right { left + width - ELLIPSIS.len() } else { left + width };
+
+    let mut window = String::new();
+    let mut window_width = 0;
+    let mut col = 0;
+    for (piece, piece_width) in display_pieces(line, tab_width) {
+        if start <= col && col + piece_width <= end {
+            // Pads the room left by the clusters cut in halves, as well as the
+            // room of the `...`.
+            window.extend(std::iter::repeat_n(' ', col - left - window_width));
+            window.push_str(&piece);
+            window_width = col + piece_width - left;
+        }
+        col += piece_width;
+    }
+    if cut_left && window_width < ELLIPSIS.len() {
+        window.extend(std::iter::repeat_n(' ', ELLIPSIS.len() - window_width));
+    }
+    if cut_right {
+        window.extend(std::iter::repeat_n(' ', width - window_width));
+    }
+    (window, cut_left, cut_right)
+}
+
+/// Splits a line into grapheme clusters as they are displayed, with their
+/// widths.
+fn display_pieces(line: &str, tab_width: usize) -> impl Iterator<Item = (Cow<'_, str>, usize)> {
+    let mut width = 0;
+    line.graphemes(true).map(move |cluster| {
+        let mut chars = cluster.chars();
+        let (piece, piece_width) = match (chars.next(), chars.next()) {
+            (Some('\t'), None) => {
+                let spaces = tab_width - width % tab_width;
+                (Cow::Owned(" ".repeat(spaces)), spaces)
+            }
+            (Some(ch), None) if is_invisible_char(ch) => {
+                let escaped = escape_invisible_char(ch);
+                let escaped_width = escaped.len();
+                (Cow::Owned(escaped), escaped_width)
+            }
+            _ => (Cow::Borrowed(cluster), grapheme_width(cluster)),
+        };
+        width += piece_width;
+        (piece, piece_width)
+    })
+}
+
+#[derive(Clone, Copy)]
+struct LineLayout {
+    /// The width of the line number column.
+    indent: usize,
+
+    /// The column where the source code starts.
+    code_col: usize,
+
+    /// The first display column of the source lines to draw, lines are cut
+    /// before it if they are too wide, see [`RenderConfig::max_width`].
+    left: usize,
+
+    /// The number of display columns available for the source lines, `None`
+    /// if the lines are not cut.
+    width: Option<usize>,
+
+    level: Level,
+}
+
+impl LineLayout {
+    /// Returns the buffer column of a display column in the source line, the
+    /// columns outside the window are moved to its edges.
+    fn column(&self, display_col: usize) -> usize {
+        let display_col = match self.width {
+            Some(width) => display_col.clamp(self.left, self.left + width - 1),
+            None => display_col,
+        };
+        self.code_col + display_col - self.left
+    }
+
+    /// Returns the buffer columns of the display columns `[start, end)` that
+    /// are inside the window, `None` if there are none.
+    fn columns(&self, start: usize, end: usize) -> Option<Range<usize>> {
+        let (start, end) = match self.width {
+            Some(width) => (start.max(self.left), end.min(self.left + width)),
+            None => (start, end),
+        };
+        (start < end).then(|| self.column(start)..self.column(end - 1) + 1)
+    }
+}
+
+/// The marker of the cut points of wide source lines.
+const ELLIPSIS: &str = "...";
+
+/// The least number of columns given to the source lines, however narrow the
+/// output is.
+const MIN_WINDOW_WIDTH: usize = 20;
+
+#[cfg(test)]
+mod snippet_tests {
+    use crate::source::{SourceMap, Pos};
+    use crate::diagnostic::{DiagnosticLabel, DiagnosticLabels, Level};
+
+    use super::Snippet;
+    use super::super::styled_buffer::StyledBuffer;
+    use super::super::RenderConfig;
+
+    fn render(source_map: &SourceMap, labels: &DiagnosticLabels) -> String {
+        render_with_config(source_map, labels, &RenderConfig::default())
+    }
+
+    fn render_with_config(source_map: &SourceMap, labels: &DiagnosticLabels, config: &RenderConfig) -> String {
+        let mut buffer = StyledBuffer::new();
+        let tab_width = config.tab_width.unwrap_or(source_map.tab_width());
+        for snippet in Snippet::group_labels(Some(source_map), labels, tab_width).0 {
+            snippet.render(&mut buffer, 1, Level::Error, config);
+        }
+        buffer.to_plain_string().trim_end().to_string()
+    }
+
+    fn label(src: &str, text: &str, len: usize, msg: &str) -> DiagnosticLabel {
+        // Position 1 is the start of the first file in the source map.
+        let start = src.find(text).unwrap() + 1;
+        DiagnosticLabel {
+            span: (start..start + len).into(),
+            message: msg.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_empty_span_at_line_start() {
+        let src = "val x = 1\nval y = x\n";
+        let source_map = SourceMap::from_string(src);
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "val y", 0, "missing `;`"),
+            sublabels: vec![label(src, "val x", 0, "")],
+        };
+        assert_eq!(render(&source_map, &labels), "\
+1 | val x = 1
+  | -
+2 | val y = x
+  | ^ missing `;`");
+    }
+
+    #[test]
+    fn test_render_empty_span_at_end_of_file() {
+        let src = "val x = 1\nval y =\n";
+        let source_map = SourceMap::new();
+        let file = source_map.load_test_file(None, src.to_string());
+        // Another file follows, so the end of the first one is not the end of
+        // the source map.
+        source_map.load_test_file(None, "val z = 2\n".to_string());
+        let end = file.end_pos().to_usize();
+        let labels = DiagnosticLabels {
+            primary_label: DiagnosticLabel {
+                span: (end..end).into(),
+                message: "expected an expression".to_string(),
+            },
+            sublabels: vec![],
+        };
+        let (snippets, unresolved) = Snippet::group_labels(Some(&source_map), &labels, 4);
+        assert!(unresolved.is_empty());
+        assert_eq!(snippets[0].location().line(), 2);
+        assert_eq!(render(&source_map, &labels), "\
+2 | val y =
+  |         ^ expected an expression");
+    }
+
+    #[test]
+    fn test_render_labels_on_same_line() {
+        let src = "fun fact n =\n  if n = 0 then 1 else n * fact (n - 1)\n  | _ => 0\n";
+        let source_map = SourceMap::from_string(src);
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "n * fact", 16, "expected `bool`"),
+            sublabels: vec![
+                label(src, "= 0", 1, "because of this"),
+                label(src, "fun", 58, "in this function"),
+            ],
+        };
+
+        assert_eq!(render(&source_map, &labels), [
+            "1 | / fun fact n =",
+            "2 | |   if n = 0 then 1 else n * fact (n - 1)",
+            "  | |        -               ^^^^^^^^^^^^^^^^ expected `bool`",
+            "  | |        |",
+            "  | |        because of this",
+            "3 | |   | _ => 0",
+            "  | |_____- in this function",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_sublabels() {
+        let src = "val x = 1\nval y = x + \"a\"\n";
+        let source_map = SourceMap::from_string(src);
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "\"a\"", 3, "expected `int`"),
+            sublabels: vec![
+                label(src, "x +", 1, "this is an `int`"),
+                label(src, "x =", 1, "defined here"),
+            ],
+        };
+
+        assert_eq!(render(&source_map, &labels), [
+            "1 | val x = 1",
+            "  |     - defined here",
+            "2 | val y = x + \"a\"",
+            "  |         -   ^^^ expected `int`",
+            "  |         |",
+            "  |         this is an `int`",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_nested_multiline_labels() {
+        let src = "fun fact n =\n  if n = 0\n  then 1\n  else 2\n";
+        let source_map = SourceMap::from_string(src);
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "fact", 37, "outer"),
+            sublabels: vec![label(src, "= 0", 12, "inner")],
+        };
+
+        assert_eq!(render(&source_map, &labels), [
+            "1 |     fun fact n =",
+            "  |  _______^",
+            "2 | |     if n = 0",
+            "  | |  ________-",
+            "3 | | |   then 1",
+            "  | | |________- inner",
+            "4 | |     else 2",
+            "  | |__________^ outer",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_labels_in_same_file() {
+        let src = "val a = 1\nval b = 2\nval c = 3\nval d = 4\nval e = 5\nval f = 6\n";
+        let source_map = SourceMap::from_string(src);
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "f =", 1, "here"),
+            sublabels: vec![label(src, "a =", 1, "first"), label(src, "c =", 1, "second")],
+        };
+
+        let (snippets, unresolved) = Snippet::group_labels(Some(&source_map), &labels, 4);
+        assert_eq!(snippets.len(), 1);
+        assert!(unresolved.is_empty());
+        assert_eq!(render(&source_map, &labels), [
+            "1 | val a = 1",
+            "  |     - first",
+            "2 | val b = 2",
+            "3 | val c = 3",
+            "  |     - second",
+            "...",
+            "6 | val f = 6",
+            "  |     ^ here",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_elided_multiline_label() {
+        let src = "structure S = struct\n  val a = 1\n  val b = 2\n  val c = 3\n  val d = 4\nend\n";
+        let source_map = SourceMap::from_string(src);
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "structure", src.len() - 1, "in this structure"),
+            sublabels: vec![label(src, "c =", 1, "here")],
+        };
+        let config = RenderConfig {
+            max_multiline_lines: 3,
+            elided_edge_lines: 1,
+            ..RenderConfig::default()
+        };
+
+        assert_eq!(render_with_config(&source_map, &labels, &config), [
+            "1 | / structure S = struct",
+            "... |",
+            "4 | |   val c = 3",
+            "  | |       - here",
+            "5 | |   val d = 4",
+            "6 | | end",
+            "  | |___^ in this structure",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_context_lines() {
+        let src = "val a = 1\nval b = 2\nval c = 3\nval d = 4\nval e = 5\nval f = 6\nval g = 7\nval h = 8\nval i = 9\n";
+        let source_map = SourceMap::from_string(src);
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "b =", 1, "here"),
+            sublabels: vec![label(src, "d =", 1, "there"), label(src, "i =", 1, "far away")],
+        };
+        let config = RenderConfig {
+            context_lines: 1,
+            ..RenderConfig::default()
+        };
+
+        assert_eq!(render_with_config(&source_map, &labels, &config), [
+            "1 | val a = 1",
+            "2 | val b = 2",
+            "  |     ^ here",
+            "3 | val c = 3",
+            "4 | val d = 4",
+            "  |     - there",
+            "5 | val e = 5",
+            "...",
+            "8 | val h = 8",
+            "9 | val i = 9",
+            "  |     - far away",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_tabs() {
+        let src = "val\tx =\t1\n";
+        let source_map = SourceMap::from_string(src);
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "1", 1, "here"),
+            sublabels: vec![label(src, "x", 1, "")],
+        };
+
+        assert_eq!(render(&source_map, &labels), [
+            "1 | val x = 1",
+            "  |     -   ^ here",
+        ].join("\n"));
+
+        let config = RenderConfig {
+            tab_width: Some(8),
+            ..RenderConfig::default()
+        };
+        assert_eq!(render_with_config(&source_map, &labels, &config), [
+            "1 | val     x =     1",
+            "  |         -       ^ here",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_grapheme_clusters() {
+        // A ZWJ sequence, a combining mark, and a flag.
+        let src = "val s = \"\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}e\u{301}\u{1F1EF}\u{1F1F5}\" + x\n";
+        let source_map = SourceMap::from_string(src);
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "x", 1, "here"),
+            sublabels: vec![label(src, family, family.len(), "")],
+        };
+
+        let x = source_map.lookup_pos_info(Pos::from_usize(src.find('x').unwrap() + 1)).unwrap();
+        assert_eq!((x.col(), x.col_display()), (22, 18));
+        assert_eq!(render(&source_map, &labels).lines().nth(1).unwrap(),
+            "  |          --       ^ here");
+    }
+
+    #[test]
+    fn test_render_invisible_chars() {
+        let src = "val s = \"a\u{202E}b\" + x\n";
+        let source_map = SourceMap::from_string(src);
+        let labels = DiagnosticLabels {
+            primary_label: label(src, "x", 1, "here"),
+            sublabels: vec![label(src, "\u{202E}", 3, "")],
+        };
+
+        let file = source_map.lookup_file_at_pos(Pos::from_usize(1)).unwrap();
+        assert_eq!(file.invisible_chars().len(), 1);
+        assert_eq!(file.invisible_chars()[0].escaped(), "<U+202E>");
+        assert_eq!(render(&source_map, &labels), [
+            "1 | val s = \"a<U+202E>b\" + x",
+            "  |           --------     ^ here",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_wide_lines() {
+        let src = format!("val x = {}f (g y){}\n", "a + ".repeat(20), " + b".repeat(20));
+        let source_map = SourceMap::from_string(&src);
+        let labels = DiagnosticLabels {
+            primary_label: label(&src, "g y", 3, "here"),
+            sublabels: vec![label(&src, "f (", 1, "")],
+        };
+        let config = RenderConfig {
+            max_width: Some(40),
+            ..RenderConfig::default()
+        };
+
+        assert_eq!(render_with_config(&source_map, &labels, &config), [
+            "1 | ...a + a + a + f (g y) + b + b + ...",
+            "  |                -  ^^^ here",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_render_wide_lines_with_distant_labels() {
+        let src = format!("val x = f {}(g y){}\n", "a + ".repeat(20), " + b".repeat(20));
+        let source_map = SourceMap::from_string(&src);
+        let config = RenderConfig {
+            max_width: Some(40),
+            ..RenderConfig::default()
+        };
+
+        // The labels don't fit together, the sublabel is cut and its message
+        // goes to the left edge.
+        let labels = DiagnosticLabels {
+            primary_label: label(&src, "g y", 3, "here"),
+            sublabels: vec![label(&src, "f a", 1, "called")],
+        };
+        assert_eq!(render_with_config(&source_map, &labels, &config), [
+            "1 | ... a + a + a + (g y) + b + b + b...",
+            "  |                  ^^^ here",
+            "  | |",
+            "  | called",
+        ].join("\n"));
+
+        // The window is anchored on the primary label, not the leftmost one.
+        let labels = DiagnosticLabels {
+            primary_label: label(&src, "b\n", 1, "last"),
+            sublabels: vec![label(&src, "f a", 1, "called")],
+        };
+        assert_eq!(render_with_config(&source_map, &labels, &config), [
+            "1 | ...b + b + b + b + b + b + b + b + b",
+            "  |                                    ^ last",
+            "  | |",
+            "  | called",
+        ].join("\n"));
+    }
+}
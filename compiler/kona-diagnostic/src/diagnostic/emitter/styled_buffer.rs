@@ -0,0 +1,124 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use crate::diagnostic::Level;
+
+/// A two-dimensional text buffer where each character carries a style.
+///
+/// Emitters lay out the whole diagnostic in this buffer before writing it
+/// out, so that the layout engine can draw marks, connectors and labels in
+/// any order, instead of printing the output strictly from left to right.
+#[derive(Debug, Default)]
+pub(crate) struct StyledBuffer {
+    lines: Vec<Vec<StyledChar>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StyledChar {
+    ch: char,
+    style: Style,
+}
+
+impl StyledChar {
+    const SPACE: StyledChar = StyledChar { ch: ' ', style: Style::Plain };
+}
+
+/// The style of a piece of rendered text, emitters decide the actual colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Style {
+    /// Source code and messages.
+    Plain,
+
+    /// The level name of a diagnostic, e.g. "error" or "warning".
+    Level(Level),
+
+    /// Line numbers, gutters and file locations.
+    LineNumber,
+
+    /// Marks and labels of the primary label.
+    Primary(Level),
+
+    /// Marks and labels of the sublabels.
+    Secondary,
+
+    /// Source lines without labels, shown as context.
+    Context,
+}
+
+impl StyledBuffer {
+    pub(crate) fn new() -> StyledBuffer {
+        StyledBuffer::default()
+    }
+
+    /// Returns the number of lines in the buffer.
+    pub(crate) fn num_lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    fn ensure_lines(&mut self, line: usize) {
+        if self.lines.len() <= line {
+            self.lines.resize(line + 1, vec![]);
+        }
+    }
+
+    /// Puts a character at the given line and column, padding the line with
+    /// spaces if necessary.
+    pub(crate) fn putc(&mut self, line: usize, col: usize, ch: char, style: Style) {
+        self.ensure_lines(line);
+        let line = &mut self.lines[line];
+        if line.len() <= col {
+            line.resize(col + 1, StyledChar::SPACE);
+        }
+        line[col] = StyledChar { ch, style };
+    }
+
+    /// Puts a string at the given line, starting at the given column.
+    pub(crate) fn puts(&mut self, line: usize, col: usize, s: &str, style: Style) {
+        for (offset, ch) in s.chars().enumerate() {
+            self.putc(line, col + offset, ch, style);
+        }
+    }
+
+    /// Appends a string at the end of the given line.
+    pub(crate) fn append(&mut self, line: usize, s: &str, style: Style) {
+        self.ensure_lines(line);
+        let col = self.lines[line].len();
+        self.puts(line, col, s, style);
+    }
+
+    /// Returns the text of the buffer without styles, each line is ended by a
+    /// line break.
+    pub(crate) fn to_plain_string(&self) -> String {
+        self.render()
+            .into_iter()
+            .map(|line| {
+                let mut line = line.into_iter().map(|(text, _)| text).collect::<String>();
+                line.push('\n');
+                line
+            })
+            .collect()
+    }
+
+    /// Returns the lines of the buffer, each line is split into runs of text
+    /// sharing the same style. Trailing spaces are removed.
+    pub(crate) fn render(&self) -> Vec<Vec<(String, Style)>> {
+        self.lines
+            .iter()
+            .map(|line| {
+                let len = line
+                    .iter()
+                    .rposition(|sc| *sc != StyledChar::SPACE)
+                    .map_or(0, |idx| idx + 1);
+
+                let mut runs: Vec<(String, Style)> = vec![];
+                for sc in &line[..len] {
+                    match runs.last_mut() {
+                        Some((text, style)) if *style == sc.style => text.push(sc.ch),
+                        _ => runs.push((sc.ch.to_string(), sc.style)),
+                    }
+                }
+                runs
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,649 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{rc::Rc, io::{self, IsTerminal}, ops::Range};
+
+use crate::source::{SourceMap, DEFAULT_TAB_WIDTH};
+use std::io::Write;
+use termcolor::{Color, ColorSpec, StandardStream};
+use terminal_size::{terminal_size_of, Width};
+
+pub use termcolor::{ColorChoice, WriteColor};
+
+use crate::diagnostic::{Diagnostic, Level, SplicedLines};
+
+use super::{Emitter, RenderConfig};
+use super::snippet::{Snippet, UnresolvedLabel, display_width, escape_line};
+use super::styled_buffer::{StyledBuffer, Style};
+
+/// Renders diagnostics for humans, with source snippets and colors.
+///
+/// It writes to stderr by default. Use [`TtyEmitter::with_writer`] for other
+/// sinks, e.g. a `termcolor::NoColor<File>`, or a `termcolor::Buffer` to test
+/// the rendered output.
+pub struct TtyEmitter {
+    out: Box<dyn WriteColor>,
+    source_map: Option<Rc<SourceMap>>,
+    config: RenderConfig,
+}
+
+impl TtyEmitter {
+    /// Creates an emitter writing to stderr, colored if it's a terminal.
+    pub fn new(source_map: Rc<SourceMap>) -> TtyEmitter {
+        TtyEmitter::stderr(Some(source_map), ColorChoice::Auto)
+    }
+
+    pub fn no_source_map() -> TtyEmitter {
+        TtyEmitter::stderr(None, ColorChoice::Auto)
+    }
+
+    /// Creates an emitter writing to stderr, see [`resolve_color_choice`] for
+    /// how `ColorChoice::Auto` is resolved. Wide source lines are cut to the
+    /// width of the terminal, if it's one.
+    pub fn stderr(source_map: Option<Rc<SourceMap>>, color: ColorChoice) -> TtyEmitter {
+        let color = resolve_color_choice(color, io::stderr().is_terminal());
+        let mut emitter = TtyEmitter::with_writer(Box::new(StandardStream::stderr(color)), source_map);
+        emitter.config.max_width = terminal_size_of(io::stderr()).map(|(Width(w), _)| w as usize);
+        emitter
+    }
+
+    /// Creates an emitter writing to stdout, see [`resolve_color_choice`] for
+    /// how `ColorChoice::Auto` is resolved. Wide source lines are cut to the
+    /// width of the terminal, if it's one.
+    pub fn stdout(source_map: Option<Rc<SourceMap>>, color: ColorChoice) -> TtyEmitter {
+        let color = resolve_color_choice(color, io::stdout().is_terminal());
+        let mut emitter = TtyEmitter::with_writer(Box::new(StandardStream::stdout(color)), source_map);
+        emitter.config.max_width = terminal_size_of(io::stdout()).map(|(Width(w), _)| w as usize);
+        emitter
+    }
+
+    /// Creates an emitter writing to any sink, colors are written if the sink
+    /// supports them.
+    pub fn with_writer(out: Box<dyn WriteColor>, source_map: Option<Rc<SourceMap>>) -> TtyEmitter {
+        TtyEmitter { out, source_map, config: RenderConfig::default() }
+    }
+
+    /// Sets the render options, including `max_width`. To keep the width
+    /// detected from the terminal, start from [`render_config`](Self::render_config).
+    pub fn with_render_config(mut self, config: RenderConfig) -> TtyEmitter {
+        self.config = config;
+        self
+    }
+
+    /// Returns the render options.
+    pub fn render_config(&self) -> &RenderConfig {
+        &self.config
+    }
+}
+
+/// Resolves `ColorChoice::Auto` from the environment:
+///
+/// - No colors if `NO_COLOR` is set to a non-empty value.
+/// - Colors if `CLICOLOR_FORCE` is set to a value other than `0`.
+/// - Otherwise, colors only if the output is a terminal.
+///
+/// Other choices, e.g. from `--color=always`, are returned unchanged.
+pub fn resolve_color_choice(choice: ColorChoice, is_terminal: bool) -> ColorChoice {
+    resolve_color_choice_with_env(choice, is_terminal, |name| std::env::var(name).ok())
+}
+
+fn resolve_color_choice_with_env(
+    choice: ColorChoice,
+    is_terminal: bool,
+    env: impl Fn(&str) -> Option<String>,
+) -> ColorChoice {
+    if choice != ColorChoice::Auto {
+        return choice;
+    }
+    if env("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        ColorChoice::Never
+    } else if env("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+        ColorChoice::Always
+    } else if is_terminal {
+        ColorChoice::Auto
+    } else {
+        ColorChoice::Never
+    }
+}
+
+impl TtyEmitter {
+    fn write_buffer(&mut self, buffer: &StyledBuffer) -> io::Result<()> {
+        for line in buffer.render() {
+            for (text, style) in line {
+                match style {
+                    Style::Plain => write!(self.out, "{}", text)?,
+                    Style::Level(level) | Style::Primary(level) => {
+                        self.with_color(level_color(level), true, |out| {
+                            write!(out, "{}", text)
+                        })?
+                    },
+                    Style::LineNumber => {
+                        self.with_color(Color::Cyan, true, |out| {
+                            write!(out, "{}", text)
+                        })?
+                    },
+                    Style::Secondary => {
+                        self.with_color(Color::Blue, true, |out| {
+                            write!(out, "{}", text)
+                        })?
+                    },
+                    Style::Context => {
+                        self.out.set_color(ColorSpec::new().set_dimmed(true))?;
+                        write!(self.out, "{}", text)?;
+                        self.out.reset()?
+                    },
+                }
+            }
+            writeln!(self.out)?;
+        }
+        self.out.flush()
+    }
+
+    // Some high-level helper functions for coloring the output. Maybe we can
+    // create a new `tty` module for console pretty printing. But these wrappers
+    // are enough for now.
+
+    fn with_color<F>(&mut self, color: Color, bold: bool, mut f: F) -> io::Result<()>
+        where F: FnMut(&mut dyn WriteColor) -> io::Result<()>
+    {
+        let mut color_spec = ColorSpec::new();
+        color_spec.set_fg(Some(color));
+        color_spec.set_bold(bold);
+        self.out.set_color(&color_spec)?;
+        f(self.out.as_mut())?;
+        self.out.reset()?;
+        Ok(())
+    }
+}
+
+/// Lays out the whole diagnostic in a styled buffer, the output is the same
+/// for all text-based emitters.
+pub(crate) fn render_diagnostic(
+    diag: &Diagnostic,
+    source_map: Option<&SourceMap>,
+    config: &RenderConfig,
+) -> StyledBuffer {
+    let mut buffer = StyledBuffer::new();
+
+    // Prints colored "error", "warning", or "note".
+
+    render_title(&mut buffer, diag.level, diag.code.as_deref(), &diag.message);
+
+    // Prints the source snippets if available, followed by the children.
+    // Children without spans are printed as footers like `= note: ...`.
+
+    let tab_width = config.tab_width
+        .or(source_map.map(SourceMap::tab_width))
+        .unwrap_or(DEFAULT_TAB_WIDTH)
+        .max(1);
+
+    let snippets = Snippet::group_labels(source_map, &diag.labels, tab_width);
+    let children = diag.children
+        .iter()
+        .map(|child| (child, Snippet::group_labels(source_map, &child.labels, tab_width)))
+        .collect::<Vec<_>>();
+
+    // Suggestions that cannot be spliced are still printed as "help"
+    // without code.
+    let suggestions = diag.suggestions
+        .iter()
+        .map(|suggestion| {
+            let spliced = source_map
+                .and_then(|source_map| suggestion.splice_lines(source_map))
+                .unwrap_or_default();
+            (suggestion, spliced)
+        })
+        .collect::<Vec<_>>();
+
+    let indent = snippets.0.iter()
+        .chain(children.iter().flat_map(|(_, (snippets, _))| snippets))
+        .map(|snippet| snippet.max_line_number(config) as usize)
+        .chain(suggestions.iter().flat_map(|(_, spliced)| spliced).map(|spliced| {
+            spliced.first_line + spliced.text.lines().count()
+        }))
+        .max()
+        .map_or(1, |line_number| line_number.to_string().len());
+
+    render_snippets(&mut buffer, &snippets.0, &snippets.1, indent, diag.level, config);
+
+    for (child, (snippets, unresolved)) in children.iter() {
+        if snippets.is_empty() && unresolved.is_empty() {
+            let row = buffer.num_lines();
+            buffer.puts(row, 0, &format!("{:indent$} = ", "", indent = indent),
+                Style::LineNumber);
+            buffer.append(row, child.level.as_str(), Style::Level(child.level));
+            buffer.append(row, ": ", Style::Plain);
+
+            // Aligns the continuation lines of a multi-line message.
+            let col = indent + 3 + child.level.as_str().len() + 2;
+            for (offset, line) in child.message.lines().enumerate() {
+                buffer.puts(row + offset, col, line, Style::Plain);
+            }
+        } else {
+            render_title(&mut buffer, child.level, None, &child.message);
+            render_snippets(&mut buffer, snippets, unresolved, indent, child.level, config);
+        }
+    }
+
+    for (suggestion, spliced) in suggestions.iter() {
+        render_title(&mut buffer, Level::Help, None, &suggestion.message);
+        render_spliced_lines(&mut buffer, spliced, indent, tab_width);
+    }
+
+    buffer
+}
+
+/// Draws the title of a diagnostic, e.g. "error[E0042]: unexpected token",
+/// where the code is optional.
+fn render_title(buffer: &mut StyledBuffer, level: Level, code: Option<&str>, message: &str) {
+    let row = buffer.num_lines();
+    buffer.append(row, level.as_str(), Style::Level(level));
+    if let Some(code) = code {
+        buffer.append(row, &format!("[{}]", code), Style::Level(level));
+    }
+    buffer.append(row, ": ", Style::Plain);
+
+    // Aligns the continuation lines of a multi-line message.
+    let col = level.as_str().len() + code.map_or(0, |code| code.len() + 2) + 2;
+    for (offset, line) in message.lines().enumerate() {
+        buffer.puts(row + offset, col, line, Style::Plain);
+    }
+}
+
+/// Draws the snippets with their "-->" headers, one per file, followed by the
+/// labels that cannot be resolved. `indent` is the width of the line number
+/// column.
+///
+/// The header columns are 1-based and count characters, like the columns of
+/// the short, JSON and SARIF emitters, so that editors can jump to them.
+fn render_snippets(
+    buffer: &mut StyledBuffer,
+    snippets: &[Snippet],
+    unresolved: &[UnresolvedLabel],
+    indent: usize,
+    level: Level,
+    config: &RenderConfig,
+) {
+    // The first snippet contains the primary label (if it could be resolved),
+    // the others only contain sublabels.
+    let arrow = |idx: usize| if idx == 0 { "-->" } else { ":::" };
+
+    for (idx, snippet) in snippets.iter().enumerate() {
+        let location = snippet.location();
+
+        // Prints "  --> src/main.sml:1:1"
+        let row = buffer.num_lines();
+        buffer.puts(row, 0, &format!("{:indent$}{} ", "", arrow(idx), indent = indent),
+            Style::LineNumber);
+        buffer.append(row, &format!("{file_name}:{line}:{col}",
+            file_name = location.name(),
+            line = location.line(),
+            // `PosInfo::col` is 0-based.
+            col = location.col() + 1,
+        ), Style::Plain);
+        buffer.puts(row + 1, 0, &format!("{:indent$} |", "", indent = indent),
+            Style::LineNumber);
+
+        snippet.render(buffer, indent, level, config);
+
+        let row = buffer.num_lines();
+        buffer.puts(row, 0, &format!("{:indent$} |", "", indent = indent),
+            Style::LineNumber);
+    }
+
+    // Prints "  ::: <span 12..40> (across source files): expected `int`"
+    for (idx, unresolved) in unresolved.iter().enumerate() {
+        let row = buffer.num_lines();
+        buffer.puts(row, 0,
+            &format!("{:indent$}{} ", "", arrow(snippets.len() + idx), indent = indent),
+            Style::LineNumber);
+        buffer.append(row,
+            &format!("<span {:?}> ({})", unresolved.label.span, unresolved.reason),
+            Style::Plain);
+        if !unresolved.label.message.is_empty() {
+            let style = if unresolved.is_primary { Style::Primary(level) } else { Style::Secondary };
+            buffer.append(row, ": ", Style::Plain);
+            buffer.append(row, &unresolved.label.message, style);
+        }
+    }
+}
+
+/// Draws the lines patched by a suggestion, with the replacements underlined
+/// by `~`. Like `rustc`, suggestions removing code are drawn as a diff instead,
+/// since removed code can't be underlined: the original lines marked by `-`,
+/// followed by the patched lines marked by `+`.
+fn render_spliced_lines(
+    buffer: &mut StyledBuffer,
+    spliced: &[SplicedLines],
+    indent: usize,
+    tab_width: usize,
+) {
+    let code_col = indent + 3;
+
+    for spliced in spliced {
+        let row = buffer.num_lines();
+        buffer.puts(row, 0, &format!("{:indent$} |", "", indent = indent),
+            Style::LineNumber);
+
+        // Draws the lines with their highlighted ranges, `~` under them if
+        // `underline` is set.
+        let mut draw = |text: &str, ranges: &[Range<usize>], sign: char, style: Style, underline: bool| {
+            let mut line_start = 0;
+            for (offset, line) in text.split('\n').enumerate() {
+                let line = line.trim_end_matches('\r');
+                let line_range = line_start..line_start + line.len();
+                line_start += line.len() + 1;
+
+                let row = buffer.num_lines();
+                buffer.puts(row, 0,
+                    &format!("{:>indent$} ", spliced.first_line + offset + 1, indent = indent),
+                    Style::LineNumber);
+                buffer.putc(row, indent + 1, sign,
+                    if sign == '|' { Style::LineNumber } else { style });
+                buffer.puts(row, code_col, &escape_line(line, tab_width), Style::Plain);
+
+                for range in ranges.iter() {
+                    let start = range.start.clamp(line_range.start, line_range.end);
+                    let end = range.end.clamp(line_range.start, line_range.end);
+                    if start >= end {
+                        continue;
+                    }
+                    let (start, end) = (start - line_range.start, end - line_range.start);
+
+                    // Tab stops only depend on the text before them, so the
+                    // escaped prefix is a prefix of the escaped line.
+                    let prefix = escape_line(&line[..start], tab_width);
+                    let highlighted = escape_line(&line[..end], tab_width);
+                    buffer.puts(row, code_col + prefix.chars().count(),
+                        &highlighted[prefix.len()..], style);
+
+                    if underline {
+                        let start_col = display_width(&line[..start], tab_width);
+                        let width = (display_width(&line[..end], tab_width) - start_col).max(1);
+                        buffer.puts(row + 1, 0, &format!("{:indent$} |", "", indent = indent),
+                            Style::LineNumber);
+                        buffer.puts(row + 1, code_col + start_col, &"~".repeat(width), style);
+                    }
+                }
+            }
+        };
+
+        let style = Style::Primary(Level::Help);
+        if spliced.deletions.is_empty() {
+            draw(&spliced.text, &spliced.highlights, '|', style, true);
+        } else {
+            draw(&spliced.original, &spliced.deletions, '-', Style::Primary(Level::Error), false);
+            // Nothing is left if whole lines are removed.
+            if !spliced.text.is_empty() {
+                draw(&spliced.text, &spliced.highlights, '+', style, false);
+            }
+        }
+
+        let row = buffer.num_lines();
+        buffer.puts(row, 0, &format!("{:indent$} |", "", indent = indent),
+            Style::LineNumber);
+    }
+}
+
+fn level_color(level: Level) -> Color {
+    match level {
+        Level::Error => Color::Red,
+        Level::Warn => Color::Yellow,
+        Level::Note => Color::Blue,
+        Level::Help => Color::Cyan,
+    }
+}
+
+impl Emitter for TtyEmitter {
+    fn emit_diagnostic(&mut self, diag: &Diagnostic) -> io::Result<()> {
+        let buffer = render_diagnostic(diag, self.source_map.as_deref(), &self.config);
+        self.write_buffer(&buffer)
+    }
+
+    fn source_map(&self) -> Option<Rc<SourceMap>> {
+        self.source_map.clone()
+    }
+}
+
+#[cfg(test)]
+mod tty_emitter_tests {
+    use std::rc::Rc;
+
+    use termcolor::{Ansi, ColorChoice, NoColor};
+
+    use crate::source::SourceMap;
+    use crate::diagnostic::{DiagnosticEngine, Applicability};
+
+    use super::{TtyEmitter, RenderConfig, resolve_color_choice_with_env};
+    use super::super::test_utils::SharedBuffer;
+
+    fn emit(engine: &DiagnosticEngine, start: u32) {
+        engine.create_warn("unused binding `x`")
+            .set_primary_label(start + 4..start + 5, "never used")
+            .emit();
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("test.sml".to_string()), "val x = 1\n".to_string());
+        let start = file.start_pos().to_u32();
+
+        let plain = SharedBuffer::default();
+        let colored = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            TtyEmitter::with_writer(Box::new(NoColor::new(plain.clone())), Some(source_map.clone())),
+        ));
+        emit(&engine, start);
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            TtyEmitter::with_writer(Box::new(Ansi::new(colored.clone())), Some(source_map)),
+        ));
+        emit(&engine, start);
+
+        assert_eq!(plain.contents(), "\
+warning: unused binding `x`
+ --> test.sml:1:5
+  |
+1 | val x = 1
+  |     ^ never used
+  |
+");
+        assert!(colored.contents().starts_with("\x1b[0m\x1b[1m\x1b[33mwarning\x1b[0m: unused binding `x`\n"));
+    }
+
+    #[test]
+    fn test_children() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("test.sml".to_string()),
+            "fun f x = x + 1\nval y = f true\n".to_string());
+        let start = file.start_pos().to_u32();
+
+        let buffer = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            TtyEmitter::with_writer(Box::new(NoColor::new(buffer.clone())), Some(source_map)),
+        ));
+        engine.create_err("mismatched types")
+            .set_primary_label(start + 26..start + 30, "expected `int`")
+            .add_span_note(start + 6..start + 7, "parameter defined here\nits type is `int`")
+            .add_help("convert the argument with\n`if b then 1 else 0`")
+            .emit();
+
+        assert_eq!(buffer.contents(), "\
+error: mismatched types
+ --> test.sml:2:11
+  |
+2 | val y = f true
+  |           ^^^^ expected `int`
+  |
+note: parameter defined here
+      its type is `int`
+ --> test.sml:1:7
+  |
+1 | fun f x = x + 1
+  |       -
+  |
+  = help: convert the argument with
+          `if b then 1 else 0`
+");
+    }
+
+    #[test]
+    fn test_render_config() {
+        let config = RenderConfig { max_width: Some(40), ..RenderConfig::default() };
+        let emitter = TtyEmitter::with_writer(Box::new(NoColor::new(SharedBuffer::default())), None)
+            .with_render_config(config.clone());
+        assert_eq!(emitter.render_config(), &config);
+
+        // An explicit `None` turns off the cutting.
+        let emitter = emitter.with_render_config(RenderConfig { max_width: None, ..config });
+        assert_eq!(emitter.render_config().max_width, None);
+    }
+
+    #[test]
+    fn test_header_columns() {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("a.sml".to_string()),
+            "val x = 1\nval 名前 = x\n".to_string());
+        let other = source_map.load_test_file(Some("b.sml".to_string()), "val y = 2\n".to_string());
+        let (start, other) = (file.start_pos().to_u32(), other.start_pos().to_u32());
+
+        let buffer = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            TtyEmitter::with_writer(Box::new(NoColor::new(buffer.clone())), Some(source_map)),
+        ));
+        engine.create_warn("first column").set_primary_label(start..start + 3, "").emit();
+        // "名前" is 6 bytes and 4 columns wide, but 2 characters.
+        engine.create_warn("after wide characters")
+            .set_primary_label(start + 23..start + 24, "")
+            .add_sublabel(other..other + 3, "")
+            .emit();
+
+        let headers = buffer.contents()
+            .lines()
+            .filter(|line| line.starts_with(" --> ") || line.starts_with(" ::: "))
+            .map(String::from)
+            .collect::<Vec<_>>();
+        assert_eq!(headers, [" --> a.sml:1:1", " --> a.sml:2:10", " ::: b.sml:1:1"]);
+    }
+
+    /// Renders a diagnostic with only a suggestion, substitutions are given
+    /// as `(text to find, its length, replacement)`.
+    fn render_suggestion(src: &str, substitutions: &[(&str, usize, &str)]) -> String {
+        let source_map = Rc::new(SourceMap::new());
+        let file = source_map.load_test_file(Some("test.sml".to_string()), src.to_string());
+        let start = file.start_pos().to_usize();
+
+        let buffer = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            TtyEmitter::with_writer(Box::new(NoColor::new(buffer.clone())), Some(source_map)),
+        ));
+        let substitutions = substitutions
+            .iter()
+            .map(|&(text, len, replacement)| {
+                let pos = start + src.find(text).unwrap();
+                ((pos..pos + len).into(), replacement.to_string())
+            })
+            .collect();
+        engine.create_warn("suggestion")
+            .add_multipart_suggestion("try this", substitutions, Applicability::MachineApplicable)
+            .emit();
+        buffer.contents()
+    }
+
+    #[test]
+    fn test_render_suggestions() {
+        // Insertion.
+        assert_eq!(render_suggestion("val x = f 1 + 2\n", &[("f 1", 0, "("), (" + 2", 0, ")")]), "\
+warning: suggestion
+help: try this
+  |
+1 | val x = (f 1) + 2
+  |         ~   ~
+  |
+");
+
+        // Replacement.
+        assert_eq!(render_suggestion("val x = f 1 + 2\n", &[("f 1", 3, "(f 1)")]), "\
+warning: suggestion
+help: try this
+  |
+1 | val x = (f 1) + 2
+  |         ~~~~~
+  |
+");
+
+        // Deletion.
+        assert_eq!(render_suggestion("val x = 1;;\n", &[(";;", 2, "")]), "\
+warning: suggestion
+help: try this
+  |
+1 - val x = 1;;
+1 + val x = 1
+  |
+");
+
+        // Multi-line replacement.
+        assert_eq!(render_suggestion("val x =\n  1\nval y = 2\n", &[("  1", 3, "  1 +\n  2")]), "\
+warning: suggestion
+help: try this
+  |
+2 |   1 +
+  | ~~~~~
+3 |   2
+  | ~~~
+  |
+");
+    }
+
+    #[test]
+    fn test_labels_in_multiple_files() {
+        let source_map = Rc::new(SourceMap::new());
+        let sig = source_map.load_test_file(Some("s.sig".to_string()), "val f : int\n".to_string());
+        let sml = source_map.load_test_file(Some("s.sml".to_string()), "val f = true\n".to_string());
+        let (sig, sml) = (sig.start_pos().to_u32(), sml.start_pos().to_u32());
+
+        let buffer = SharedBuffer::default();
+        let engine = DiagnosticEngine::with_emitter(Box::new(
+            TtyEmitter::with_writer(Box::new(NoColor::new(buffer.clone())), Some(source_map)),
+        ));
+        engine.create_err("value does not match its specification")
+            .set_primary_label(sml + 8..sml + 12, "has type `bool`")
+            .add_sublabel(sig + 8..sig + 11, "expected `int`")
+            .add_sublabel(sig + 4..sml + 5, "")
+            .emit();
+
+        assert_eq!(buffer.contents(), format!("\
+error: value does not match its specification
+ --> s.sml:1:9
+  |
+1 | val f = true
+  |         ^^^^ has type `bool`
+  |
+ ::: s.sig:1:9
+  |
+1 | val f : int
+  |         --- expected `int`
+  |
+ ::: <span {}..{}> (across source files)
+", sig + 4, sml + 5));
+    }
+
+    #[test]
+    fn test_resolve_color_choice() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| vars
+                .iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        };
+
+        assert_eq!(resolve_color_choice_with_env(ColorChoice::Auto, true, env(&[])), ColorChoice::Auto);
+        assert_eq!(resolve_color_choice_with_env(ColorChoice::Auto, false, env(&[])), ColorChoice::Never);
+        assert_eq!(resolve_color_choice_with_env(ColorChoice::Auto, true, env(&[("NO_COLOR", "1")])),
+            ColorChoice::Never);
+        assert_eq!(resolve_color_choice_with_env(ColorChoice::Auto, false, env(&[("CLICOLOR_FORCE", "1")])),
+            ColorChoice::Always);
+        assert_eq!(resolve_color_choice_with_env(ColorChoice::Always, false, env(&[("NO_COLOR", "1")])),
+            ColorChoice::Always);
+    }
+}
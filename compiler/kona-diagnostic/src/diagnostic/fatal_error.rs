@@ -0,0 +1,32 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::panic::{self, AssertUnwindSafe};
+
+/// A marker that the compilation is aborted, raised by
+/// [`DiagnosticEngine::abort_if_errors`] and when the error limit is reached.
+///
+/// The errors are already reported when it is raised, it unwinds the stack up
+/// to the nearest [`FatalError::catch`] without printing a panic message.
+///
+/// [`DiagnosticEngine::abort_if_errors`]: super::DiagnosticEngine::abort_if_errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FatalError;
+
+impl FatalError {
+    /// Aborts the compilation by unwinding with `FatalError` as the payload.
+    pub fn raise(self) -> ! {
+        panic::resume_unwind(Box::new(self))
+    }
+
+    /// Runs `f`, returns `Err(FatalError)` if it raised a `FatalError`. Other
+    /// panics are propagated.
+    pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, FatalError> {
+        panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| {
+            match payload.downcast::<FatalError>() {
+                Ok(fatal) => *fatal,
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        })
+    }
+}
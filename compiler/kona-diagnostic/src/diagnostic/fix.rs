@@ -0,0 +1,388 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{rc::Rc, io, fs, fmt::Write, ops::Range};
+
+use crate::source::{Pos, SourceFile, SourceMap};
+
+use super::{Applicability, CodeSuggestion, Substitution};
+
+/// Machine-applicable suggestions grouped by source file, ready to be applied.
+///
+/// Suggestions are accepted one by one. A suggestion is skipped as a whole if
+/// any of its substitutions overlaps a substitution accepted before, or if
+/// its spans cannot be resolved in the source map. Suggestions that are not
+/// machine-applicable are ignored.
+#[derive(Debug, Default)]
+pub struct Fixes {
+    files: Vec<FileFix>,
+    skipped: Vec<CodeSuggestion>,
+}
+
+/// The substitutions to apply to a single source file.
+#[derive(Debug, Clone)]
+pub struct FileFix {
+    file: Rc<SourceFile>,
+
+    /// Non-overlapping substitutions, sorted by position.
+    substitutions: Vec<Substitution>,
+}
+
+impl Fixes {
+    pub fn new(
+        source_map: &SourceMap,
+        suggestions: impl IntoIterator<Item = CodeSuggestion>,
+    ) -> Fixes {
+        let mut fixes = Fixes::default();
+
+        for suggestion in suggestions {
+            if suggestion.applicability != Applicability::MachineApplicable {
+                continue;
+            }
+            if !fixes.try_accept(source_map, &suggestion) {
+                fixes.skipped.push(suggestion);
+            }
+        }
+        fixes
+    }
+
+    fn try_accept(&mut self, source_map: &SourceMap, suggestion: &CodeSuggestion) -> bool {
+        let resolved = suggestion.substitutions
+            .iter()
+            .map(|sub| Some((source_map.lookup_file_at_span(sub.span).ok()?, sub)))
+            .collect::<Option<Vec<_>>>();
+        let resolved = match resolved {
+            Some(resolved) => resolved,
+            None => return false,
+        };
+
+        // The same suggestion may be emitted more than once, e.g. for each
+        // use of a deprecated name. Substitutions that are already accepted
+        // are not considered as conflicts.
+        let mut accepted: Vec<(Rc<SourceFile>, &Substitution)> = vec![];
+        for (file, sub) in resolved {
+            let existing = self.file_fix(&file).map_or(&[][..], |fix| &fix.substitutions);
+            if existing.contains(sub) {
+                continue;
+            }
+            let overlaps = existing
+                .iter()
+                .chain(accepted.iter().map(|(_, sub)| *sub))
+                .any(|other| overlaps(other, sub));
+            if overlaps {
+                return false;
+            }
+            accepted.push((file, sub));
+        }
+
+        for (file, sub) in accepted {
+            let idx = match self.files.iter().position(|fix| fix.file.is_same_file(&file)) {
+                Some(idx) => idx,
+                None => {
+                    self.files.push(FileFix { file, substitutions: vec![] });
+                    self.files.len() - 1
+                }
+            };
+            let substitutions = &mut self.files[idx].substitutions;
+            let at = substitutions.partition_point(|other| other.span < sub.span);
+            substitutions.insert(at, sub.clone());
+        }
+        true
+    }
+
+    fn file_fix(&self, file: &SourceFile) -> Option<&FileFix> {
+        self.files.iter().find(|fix| fix.file.is_same_file(file))
+    }
+
+    /// Returns the fixes of each file.
+    pub fn files(&self) -> &[FileFix] {
+        &self.files
+    }
+
+    /// Returns the suggestions skipped because they overlap other suggestions
+    /// or cannot be resolved in the source map.
+    pub fn skipped(&self) -> &[CodeSuggestion] {
+        &self.skipped
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Rewrites all the affected local files.
+    pub fn apply(&self) -> io::Result<()> {
+        for fix in self.files.iter() {
+            fix.apply()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the unified diff of all the affected files.
+    pub fn unified_diff(&self) -> String {
+        self.files.iter().map(FileFix::unified_diff).collect()
+    }
+}
+
+/// Two substitutions overlap if they replace a common character, or if they
+/// insert text at the same position (we cannot tell which one goes first).
+fn overlaps(a: &Substitution, b: &Substitution) -> bool {
+    if a.span.start() == b.span.start() {
+        return true;
+    }
+    a.span.start() < b.span.end() && b.span.start() < a.span.end()
+}
+
+impl FileFix {
+    pub fn file(&self) -> &Rc<SourceFile> {
+        &self.file
+    }
+
+    pub fn substitutions(&self) -> &[Substitution] {
+        &self.substitutions
+    }
+
+    /// Returns the source code of the file with all substitutions applied.
+    pub fn fixed_source(&self) -> String {
+        self.splice(self.file.start_pos(), self.file.end_pos(), &self.substitutions)
+    }
+
+    /// Writes the fixed source code back to the file. Only local files can be
+    /// written.
+    pub fn apply(&self) -> io::Result<()> {
+        let path = self.file.local_path().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput,
+                format!("`{}` is not a local file", self.file.name()))
+        })?;
+        fs::write(path, self.fixed_source())
+    }
+
+    /// Applies the given sorted substitutions to the source code between
+    /// `start` and `end`.
+    fn splice(&self, start: Pos, end: Pos, substitutions: &[Substitution]) -> String {
+        let src = self.file.src();
+        let offset = |pos: Pos| pos.to_usize() - self.file.start_pos().to_usize();
+
+        let mut text = String::new();
+        let mut cursor = start;
+        for sub in substitutions {
+            text.push_str(&src[offset(cursor)..offset(sub.span.start())]);
+            text.push_str(&sub.replacement);
+            cursor = sub.span.end();
+        }
+        text.push_str(&src[offset(cursor)..offset(end)]);
+        text
+    }
+
+    /// Returns the changes as a unified diff, with 3 lines of context.
+    pub fn unified_diff(&self) -> String {
+        const CONTEXT: usize = 3;
+
+        if self.substitutions.is_empty() {
+            return String::new();
+        }
+
+        let file = &self.file;
+        let old = file.src();
+        let new = self.fixed_source();
+        let offset = |pos: Pos| pos.to_usize() - file.start_pos().to_usize();
+        let is_line_start = |text: &str, idx: usize| {
+            idx == 0 || idx == text.len() || text.as_bytes()[idx - 1] == b'\n'
+        };
+
+        // Groups the substitutions into changes of whole lines, as byte ranges
+        // of the old and the new source. A change is extended line by line
+        // until it ends at a line start in both sources, e.g. a substitution
+        // removing a line break also changes the next line.
+        let mut changes: Vec<(usize, usize, usize, usize)> = vec![];
+        // The difference between the byte offsets of the new and old source.
+        let mut delta: isize = 0;
+        let mut subs = self.substitutions.iter().peekable();
+        while let Some(first) = subs.peek() {
+            let mut old_end = offset(first.span.start());
+            let old_start = old[..old_end].rfind('\n').map_or(0, |idx| idx + 1);
+            let new_start = (old_start as isize + delta) as usize;
+            loop {
+                while let Some(sub) = subs.next_if(|sub| offset(sub.span.start()) <= old_end) {
+                    let (start, end) = (offset(sub.span.start()), offset(sub.span.end()));
+                    delta += sub.replacement.len() as isize - (end - start) as isize;
+                    old_end = old_end.max(end);
+                }
+                let new_end = (old_end as isize + delta) as usize;
+                if is_line_start(&old, old_end) && is_line_start(&new, new_end) {
+                    changes.push((old_start, old_end, new_start, new_end));
+                    break;
+                }
+                old_end = old[old_end..].find('\n').map_or(old.len(), |idx| old_end + idx + 1);
+            }
+        }
+
+        // Converts the byte ranges to line ranges, the last line of a source
+        // may have no line break. The sources are the same between the
+        // changes, so the context lines are taken from the old one.
+        let line_of = |text: &str, idx: usize| {
+            let unterminated = idx == text.len() && !text.is_empty() && !text.ends_with('\n');
+            text[..idx].matches('\n').count() + unterminated as usize
+        };
+        let changes = changes
+            .into_iter()
+            .map(|(old_start, old_end, new_start, new_end)| {
+                (line_of(&old, old_start)..line_of(&old, old_end),
+                    line_of(&new, new_start)..line_of(&new, new_end))
+            })
+            .collect::<Vec<_>>();
+        let old_lines = old.split_inclusive('\n').collect::<Vec<_>>();
+        let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+
+        // Groups the changes into hunks, changes whose contexts overlap are
+        // put in the same hunk.
+        let mut hunks: Vec<&[(Range<usize>, Range<usize>)]> = vec![];
+        let mut rest = &changes[..];
+        while !rest.is_empty() {
+            let len = 1 + rest
+                .windows(2)
+                .take_while(|w| w[0].0.end + 2 * CONTEXT > w[1].0.start)
+                .count();
+            let (hunk, tail) = rest.split_at(len);
+            hunks.push(hunk);
+            rest = tail;
+        }
+
+        let mut diff = String::new();
+        let name = file.name();
+        writeln!(diff, "--- a/{}", name).unwrap();
+        writeln!(diff, "+++ b/{}", name).unwrap();
+
+        for hunk in hunks {
+            let (first_old, first_new) = &hunk[0];
+            let old_first = first_old.start.saturating_sub(CONTEXT);
+            let new_first = first_new.start - (first_old.start - old_first);
+            let old_last = (hunk[hunk.len() - 1].0.end + CONTEXT).min(old_lines.len());
+
+            let mut body = vec![];
+            let mut line = old_first;
+            for (old_range, new_range) in hunk {
+                body.extend(old_lines[line..old_range.start].iter().map(|text| (' ', *text)));
+                body.extend(old_lines[old_range.clone()].iter().map(|text| ('-', *text)));
+                body.extend(new_lines[new_range.clone()].iter().map(|text| ('+', *text)));
+                line = old_range.end;
+            }
+            body.extend(old_lines[line..old_last].iter().map(|text| (' ', *text)));
+
+            let old_len = body.iter().filter(|(sign, _)| *sign != '+').count();
+            let new_len = body.iter().filter(|(sign, _)| *sign != '-').count();
+            // An empty range starts at the line before it.
+            let start = |first: usize, len: usize| if len == 0 { first } else { first + 1 };
+            writeln!(diff, "@@ -{},{} +{},{} @@",
+                start(old_first, old_len), old_len, start(new_first, new_len), new_len).unwrap();
+            for (sign, text) in body {
+                diff.push(sign);
+                diff.push_str(text);
+                // Only the last line of a source may have no line break.
+                if !text.ends_with('\n') {
+                    diff.push_str("\n\\ No newline at end of file\n");
+                }
+            }
+        }
+        diff
+    }
+}
+
+#[cfg(test)]
+mod fix_tests {
+    use crate::source::SourceMap;
+    use crate::diagnostic::{Applicability, CodeSuggestion, Substitution};
+
+    use super::Fixes;
+
+    fn suggestion(edits: &[(u32, u32, &str)], applicability: Applicability) -> CodeSuggestion {
+        CodeSuggestion {
+            message: String::new(),
+            substitutions: edits
+                .iter()
+                .map(|&(start, end, replacement)| Substitution {
+                    span: (start..end).into(),
+                    replacement: replacement.to_string(),
+                })
+                .collect(),
+            applicability,
+        }
+    }
+
+    #[test]
+    fn test_fixes_skip_overlapping_suggestions() {
+        let source_map = SourceMap::from_string("val x = 1\nval y = 2\n");
+        let fixes = Fixes::new(&source_map, vec![
+            suggestion(&[(5, 6, "a")], Applicability::MachineApplicable),
+            suggestion(&[(5, 6, "a")], Applicability::MachineApplicable),
+            suggestion(&[(15, 16, "b"), (5, 7, "c")], Applicability::MachineApplicable),
+            suggestion(&[(15, 16, "d")], Applicability::MaybeIncorrect),
+            suggestion(&[(19, 20, "3")], Applicability::MachineApplicable),
+        ]);
+
+        assert_eq!(fixes.skipped().len(), 1);
+        assert_eq!(fixes.files().len(), 1);
+        assert_eq!(fixes.files()[0].fixed_source(), "val a = 1\nval y = 3\n");
+    }
+
+    #[test]
+    fn test_fixes_unified_diff() {
+        let src = (1..=10).map(|n| format!("val x{} = {}\n", n, n)).collect::<String>();
+        let source_map = SourceMap::from_string(src);
+        let line_start = |line: u32| 1 + (line - 1) * 11;
+        let fixes = Fixes::new(&source_map, vec![
+            suggestion(&[(line_start(2) + 4, line_start(2) + 6, "y2")],
+                Applicability::MachineApplicable),
+            suggestion(&[(line_start(9) + 9, line_start(9) + 11, "9\nval z = 0\n")],
+                Applicability::MachineApplicable),
+        ]);
+
+        assert_eq!(fixes.unified_diff(), [
+            "--- a/<string>",
+            "+++ b/<string>",
+            "@@ -1,5 +1,5 @@",
+            " val x1 = 1",
+            "-val x2 = 2",
+            "+val y2 = 2",
+            " val x3 = 3",
+            " val x4 = 4",
+            " val x5 = 5",
+            "@@ -6,5 +6,6 @@",
+            " val x6 = 6",
+            " val x7 = 7",
+            " val x8 = 8",
+            "-val x9 = 9",
+            "+val x9 = 9",
+            "+val z = 0",
+            " val x10 = 10",
+            "",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn test_fixes_unified_diff_joining_lines() {
+        let src = "val x =\n  1\nval y = 2\nval z = 3\n";
+        let source_map = SourceMap::from_string(src);
+        // Position 1 is the start of the first file in the source map.
+        let pos = |text: &str| src.find(text).unwrap() as u32 + 1;
+        let fixes = Fixes::new(&source_map, vec![
+            suggestion(&[(pos("\n  1"), pos("1"), " ")], Applicability::MachineApplicable),
+            suggestion(&[(pos("\nval z") + 10, pos("\nval z") + 11, "")],
+                Applicability::MachineApplicable),
+        ]);
+
+        assert_eq!(fixes.files()[0].fixed_source(), "val x = 1\nval y = 2\nval z = 3");
+        assert_eq!(fixes.unified_diff(), [
+            "--- a/<string>",
+            "+++ b/<string>",
+            "@@ -1,4 +1,3 @@",
+            "-val x =",
+            "-  1",
+            "+val x = 1",
+            " val y = 2",
+            "-val z = 3",
+            "+val z = 3",
+            "\\ No newline at end of file",
+            "",
+        ].join("\n"));
+    }
+}
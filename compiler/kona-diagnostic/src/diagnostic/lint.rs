@@ -0,0 +1,275 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{borrow::Cow, collections::HashMap};
+
+use super::{Diagnostic, Level};
+
+/// The name of the lint group containing all warnings, e.g. `-D warnings`
+/// turns every warning into an error.
+pub const WARNINGS: &str = "warnings";
+
+/// A named lint, e.g. `unused-binding`.
+///
+/// Lints are declared as statics by the compiler and registered with
+/// [`DiagnosticEngine::register_lints`]. Their diagnostics are created by
+/// [`DiagnosticEngine::create_lint`], the level is decided by the lint level
+/// table on emission.
+///
+/// [`DiagnosticEngine::register_lints`]: super::DiagnosticEngine::register_lints
+/// [`DiagnosticEngine::create_lint`]: super::DiagnosticEngine::create_lint
+#[derive(Debug, PartialEq, Eq)]
+pub struct Lint {
+    /// The name in kebab-case, used on the command line.
+    pub name: &'static str,
+
+    /// The level if it's not set on the command line.
+    pub default_level: LintLevel,
+
+    /// A short description of what the lint checks.
+    pub description: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LintLevel {
+    /// The lint is not emitted.
+    Allow,
+
+    /// The lint is emitted as a warning.
+    Warn,
+
+    /// The lint is emitted as an error.
+    Deny,
+
+    /// Like `Deny`, but can't be lowered later, e.g. `-F x -A x` still
+    /// denies `x`.
+    Forbid,
+}
+
+impl LintLevel {
+    /// Returns the command-line flag that sets this level, e.g. "-D".
+    pub fn flag(self) -> &'static str {
+        match self {
+            LintLevel::Allow => "-A",
+            LintLevel::Warn => "-W",
+            LintLevel::Deny => "-D",
+            LintLevel::Forbid => "-F",
+        }
+    }
+
+    /// Returns the diagnostic level of the lints at this level, or `None` if
+    /// they are not emitted.
+    fn to_level(self) -> Option<Level> {
+        match self {
+            LintLevel::Allow => None,
+            LintLevel::Warn => Some(Level::Warn),
+            LintLevel::Deny | LintLevel::Forbid => Some(Level::Error),
+        }
+    }
+}
+
+/// Where the level of a lint comes from, explained in a note of the lint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LintSource {
+    Default,
+    CommandLine,
+    Warnings,
+}
+
+/// The lint level table of a [`DiagnosticEngine`](super::DiagnosticEngine).
+#[derive(Debug, Default)]
+pub(crate) struct LintLevels {
+    lints: HashMap<&'static str, &'static Lint>,
+
+    /// The level set for each lint or group, with the position of the flag
+    /// that set it, so that the last flag wins.
+    levels: HashMap<String, (LintLevel, usize)>,
+
+    /// The position of the next flag.
+    next: usize,
+}
+
+impl LintLevels {
+    pub(crate) fn register(&mut self, lints: &[&'static Lint]) {
+        for lint in lints {
+            self.lints.insert(lint.name, lint);
+        }
+    }
+
+    pub(crate) fn find(&self, name: &str) -> Option<&'static Lint> {
+        self.lints.get(name).copied()
+    }
+
+    /// Sets the level of a lint or of the `warnings` group, overriding the
+    /// levels set earlier, e.g. `-D warnings -W x` warns about `x`, while `-W
+    /// x -D warnings` denies it. Forbidden lints stay forbidden, including the
+    /// warnings after `-F warnings`.
+    pub(crate) fn set(&mut self, name: &str, level: LintLevel) {
+        if self.find(name).is_some_and(|lint| self.level(lint) == LintLevel::Forbid) {
+            return;
+        }
+        let position = self.next;
+        self.next += 1;
+        let current = self.levels.entry(name.to_string()).or_insert((level, position));
+        if current.0 != LintLevel::Forbid {
+            *current = (level, position);
+        }
+    }
+
+    pub(crate) fn level(&self, lint: &Lint) -> LintLevel {
+        self.level_and_source(lint).0
+    }
+
+    fn level_and_source(&self, lint: &Lint) -> (LintLevel, LintSource) {
+        let (level, position, source) = match self.levels.get(lint.name) {
+            Some(&(level, position)) => (level, Some(position), LintSource::CommandLine),
+            None => (lint.default_level, None, LintSource::Default),
+        };
+        match self.levels.get(WARNINGS) {
+            Some(&(warnings, warnings_position))
+                if level == LintLevel::Warn
+                    && (position < Some(warnings_position) || warnings == LintLevel::Forbid) =>
+            {
+                (warnings, LintSource::Warnings)
+            }
+            _ => (level, source),
+        }
+    }
+
+    /// Applies the table to a diagnostic before it's emitted. Returns `None`
+    /// if it's allowed, or the diagnostic with its final level.
+    pub(crate) fn apply<'d>(&self, diag: &'d Diagnostic) -> Option<Cow<'d, Diagnostic>> {
+        match diag.lint {
+            Some(lint) => {
+                let (level, source) = self.level_and_source(lint);
+                let diag_level = level.to_level()?;
+                let mut diag = diag.clone();
+                diag.level = diag_level;
+                diag.add_note_once(match source {
+                    LintSource::Default => format!(
+                        "`{} {}` is on by default", level.flag(), lint.name),
+                    LintSource::CommandLine => format!(
+                        "requested on the command line with `{} {}`", level.flag(), lint.name),
+                    LintSource::Warnings => format!(
+                        "`{} {}` implied by `{} {}`", level.flag(), lint.name, level.flag(), WARNINGS),
+                });
+                Some(Cow::Owned(diag))
+            }
+            None if diag.level == Level::Warn => match self.levels.get(WARNINGS) {
+                Some(&(warnings, _)) => {
+                    let diag_level = warnings.to_level()?;
+                    let mut diag = diag.clone();
+                    diag.level = diag_level;
+                    Some(Cow::Owned(diag))
+                }
+                None => Some(Cow::Borrowed(diag)),
+            },
+            None => Some(Cow::Borrowed(diag)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod lint_tests {
+    use crate::diagnostic::{DiagnosticEngine, BufferEmitter, Level};
+
+    use super::{Lint, LintLevel, WARNINGS};
+
+    static UNUSED_BINDING: Lint = Lint {
+        name: "unused-binding",
+        default_level: LintLevel::Warn,
+        description: "detects bindings that are never used",
+    };
+
+    static SHADOWED_BINDING: Lint = Lint {
+        name: "shadowed-binding",
+        default_level: LintLevel::Allow,
+        description: "detects bindings that shadow another binding",
+    };
+
+    #[test]
+    fn test_lint_levels() {
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+        engine.register_lints(&[&UNUSED_BINDING, &SHADOWED_BINDING]);
+
+        engine.create_lint(&UNUSED_BINDING, "unused binding `x`").emit();
+        engine.create_lint(&SHADOWED_BINDING, "`x` shadows a binding").emit();
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].level, Level::Warn);
+        assert_eq!(diagnostics[0].children[0].message, "`-W unused-binding` is on by default");
+
+        engine.set_lint_level(WARNINGS, LintLevel::Deny);
+        engine.set_lint_level("shadowed-binding", LintLevel::Forbid);
+        engine.set_lint_level("shadowed-binding", LintLevel::Allow);
+        engine.create_lint(&UNUSED_BINDING, "unused binding `x`").emit();
+        engine.create_lint(&SHADOWED_BINDING, "`x` shadows a binding").emit();
+        engine.create_warn("not a lint").emit();
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics.len(), 3);
+        assert!(diagnostics.iter().all(|diag| diag.level == Level::Error));
+        assert_eq!(diagnostics[0].children[0].message, "`-D unused-binding` implied by `-D warnings`");
+        assert_eq!(diagnostics[1].children[0].message,
+            "requested on the command line with `-F shadowed-binding`");
+        assert_eq!(engine.err_count(), 3);
+
+        engine.set_lint_level(WARNINGS, LintLevel::Allow);
+        engine.create_warn("not a lint").emit();
+        assert!(engine.drain_diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_last_flag_wins() {
+        // `-D warnings -W unused-binding`
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+        engine.register_lints(&[&UNUSED_BINDING]);
+        engine.set_lint_level(WARNINGS, LintLevel::Deny);
+        engine.set_lint_level("unused-binding", LintLevel::Warn);
+        engine.create_lint(&UNUSED_BINDING, "unused binding `x`").emit();
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics[0].level, Level::Warn);
+        assert_eq!(diagnostics[0].children[0].message,
+            "requested on the command line with `-W unused-binding`");
+
+        // `-W unused-binding -D warnings`
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+        engine.register_lints(&[&UNUSED_BINDING]);
+        engine.set_lint_level("unused-binding", LintLevel::Warn);
+        engine.set_lint_level(WARNINGS, LintLevel::Deny);
+        engine.create_lint(&UNUSED_BINDING, "unused binding `x`").emit();
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics[0].level, Level::Error);
+        assert_eq!(diagnostics[0].children[0].message, "`-D unused-binding` implied by `-D warnings`");
+
+        // `-D warnings -A unused-binding -W unused-binding`
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+        engine.register_lints(&[&UNUSED_BINDING]);
+        engine.set_lint_level(WARNINGS, LintLevel::Deny);
+        engine.set_lint_level("unused-binding", LintLevel::Allow);
+        engine.set_lint_level("unused-binding", LintLevel::Warn);
+        engine.create_lint(&UNUSED_BINDING, "unused binding `x`").emit();
+        assert_eq!(engine.drain_diagnostics()[0].level, Level::Warn);
+
+        // `-F warnings -W unused-binding -A unused-binding -W shadowed-binding`
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+        engine.register_lints(&[&UNUSED_BINDING, &SHADOWED_BINDING]);
+        engine.set_lint_level(WARNINGS, LintLevel::Forbid);
+        engine.set_lint_level("unused-binding", LintLevel::Warn);
+        engine.set_lint_level("unused-binding", LintLevel::Allow);
+        engine.set_lint_level("shadowed-binding", LintLevel::Warn);
+        assert_eq!(engine.lint_level(&UNUSED_BINDING), LintLevel::Forbid);
+        assert_eq!(engine.lint_level(&SHADOWED_BINDING), LintLevel::Forbid);
+    }
+
+    #[test]
+    fn test_replayed_lints() {
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+        engine.register_lints(&[&UNUSED_BINDING]);
+        engine.create_lint(&UNUSED_BINDING, "unused binding `x`").emit();
+        let diagnostics = engine.drain_diagnostics();
+
+        engine.replay_diagnostics(diagnostics.clone());
+        assert_eq!(engine.drain_diagnostics(), diagnostics);
+        assert_eq!(diagnostics[0].children.len(), 1);
+    }
+}
@@ -3,12 +3,27 @@
 
 //! Diagnostics and error reporting.
 
+mod baseline;
+#[allow(clippy::module_inception)]
 mod diagnostic;
 mod diagnostic_engine;
 mod diagnostic_builder;
 mod emitter;
+mod fatal_error;
+mod fix;
+mod lint;
+mod registry;
+mod suggestion;
+mod suppression;
 
+pub use baseline::*;
 pub use diagnostic::*;
 pub use diagnostic_engine::*;
 pub use diagnostic_builder::*;
 pub use emitter::*;
+pub use fatal_error::*;
+pub use fix::*;
+pub use lint::*;
+pub use registry::*;
+pub use suggestion::*;
+pub use suppression::*;
@@ -0,0 +1,38 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::collections::HashMap;
+
+/// Long-form explanations of the error codes, e.g. `E0042`, in Markdown.
+///
+/// Once a registry is set on the [`DiagnosticEngine`], emitting a diagnostic
+/// with a code that is not registered is a bug, see
+/// [`DiagnosticEngine::with_registry`].
+///
+/// [`DiagnosticEngine`]: super::DiagnosticEngine
+/// [`DiagnosticEngine::with_registry`]: super::DiagnosticEngine::with_registry
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    explanations: HashMap<&'static str, &'static str>,
+}
+
+impl Registry {
+    /// Creates a registry from `(code, explanation)` pairs.
+    pub fn new(explanations: &[(&'static str, &'static str)]) -> Registry {
+        Registry { explanations: explanations.iter().copied().collect() }
+    }
+
+    /// Returns the explanation of the code, or `None` if it's not registered.
+    pub fn find_explanation(&self, code: &str) -> Option<&'static str> {
+        self.explanations.get(code).copied()
+    }
+
+    pub fn is_registered(&self, code: &str) -> bool {
+        self.explanations.contains_key(code)
+    }
+
+    /// Returns all registered codes, in no particular order.
+    pub fn codes(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.explanations.keys().copied()
+    }
+}
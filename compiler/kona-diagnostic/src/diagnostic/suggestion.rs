@@ -0,0 +1,197 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{rc::Rc, ops::Range};
+
+use crate::source::{Pos, Span, SourceMap, SourceFile};
+
+/// A suggested code change that may fix the problem reported by a diagnostic.
+///
+/// A suggestion consists of one or more substitutions, they are supposed to be
+/// applied together. For example, adding parentheses around an expression is a
+/// suggestion with two substitutions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeSuggestion {
+    pub message: String,
+    pub substitutions: Vec<Substitution>,
+    pub applicability: Applicability,
+}
+
+/// Replaces the source code in `span` with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    pub span: Span,
+    pub replacement: String,
+}
+
+/// Indicates how confident we are that a suggestion is correct, and whether
+/// tools can apply it without asking the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Applicability {
+    /// The suggestion is definitely what the user intended, tools can apply it
+    /// automatically.
+    MachineApplicable,
+
+    /// The suggestion may be what the user intended, but it is uncertain. The
+    /// result should compile, but it may not work as expected.
+    MaybeIncorrect,
+
+    /// The suggestion contains placeholders like `(* ... *)` that must be
+    /// filled in by the user.
+    HasPlaceholders,
+}
+
+impl Applicability {
+    /// Returns the name of this applicability in exported diagnostics, e.g.
+    /// "machine-applicable".
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "machine-applicable",
+            Applicability::MaybeIncorrect => "maybe-incorrect",
+            Applicability::HasPlaceholders => "has-placeholders",
+        }
+    }
+}
+
+/// Some source lines with the substitutions of a suggestion applied.
+#[derive(Debug, Clone)]
+pub struct SplicedLines {
+    pub file: Rc<SourceFile>,
+
+    /// The 0-based index of the first original line.
+    pub first_line: usize,
+
+    /// The patched source text, without the trailing line break.
+    pub text: String,
+
+    /// The byte ranges of the replacements in `text`.
+    pub highlights: Vec<Range<usize>>,
+
+    /// The original source text of the lines, without the trailing line
+    /// break.
+    pub original: String,
+
+    /// The byte ranges in `original` removed by substitutions without
+    /// replacement.
+    pub deletions: Vec<Range<usize>>,
+}
+
+impl CodeSuggestion {
+    /// Applies the substitutions to the lines they cover, so that we can show
+    /// the user what the code looks like after the change.
+    ///
+    /// Substitutions are grouped by file, each file yields one [`SplicedLines`]
+    /// covering all lines from the first substitution to the last. Returns
+    /// `None` if any span cannot be resolved in the source map, or if two
+    /// substitutions overlap.
+    pub fn splice_lines(&self, source_map: &SourceMap) -> Option<Vec<SplicedLines>> {
+        let mut substitutions = self.substitutions
+            .iter()
+            .map(|sub| Some((source_map.lookup_file_at_span(sub.span).ok()?, sub)))
+            .collect::<Option<Vec<_>>>()?;
+        substitutions.sort_by_key(|(file, sub)| (file.start_pos(), sub.span));
+
+        let mut spliced = vec![];
+        let mut rest = &substitutions[..];
+        while let Some((file, _)) = rest.first() {
+            let len = rest
+                .iter()
+                .take_while(|(other, _)| other.is_same_file(file))
+                .count();
+            let (group, tail) = rest.split_at(len);
+            rest = tail;
+
+            let subs = group.iter().map(|(_, sub)| *sub).collect::<Vec<_>>();
+            if subs.windows(2).any(|w| w[0].span.end() > w[1].span.start()) {
+                return None;
+            }
+
+            let first = subs.first()?.span;
+            let last = subs.last()?.span;
+            let first_line = file.lookup_line_at_pos(first.start())?;
+            let last_line = file
+                .lookup_line_at_pos(last.end().max(last.start() + 1u32) - 1u32)?;
+            let lines_start = file.lookup_line_span(first_line).start();
+            let lines_end = file.lookup_line_span(last_line).end();
+
+            let src = file.src();
+            let offset = |pos: Pos| pos.to_usize() - file.start_pos().to_usize();
+
+            let mut text = String::new();
+            let mut highlights = vec![];
+            let mut deletions = vec![];
+            let mut cursor = lines_start;
+            for sub in subs {
+                if sub.replacement.is_empty() && sub.span.start() < sub.span.end() {
+                    let base = offset(lines_start);
+                    deletions.push(offset(sub.span.start()) - base..offset(sub.span.end()) - base);
+                }
+                text.push_str(&src[offset(cursor)..offset(sub.span.start())]);
+                if !sub.replacement.is_empty() {
+                    highlights.push(text.len()..text.len() + sub.replacement.len());
+                }
+                text.push_str(&sub.replacement);
+                cursor = sub.span.end();
+            }
+            text.push_str(&src[offset(cursor)..offset(lines_end)]);
+
+            let trimmed_len = text.trim_end_matches(['\n', '\r']).len();
+            text.truncate(trimmed_len);
+            let original = src[offset(lines_start)..offset(lines_end)]
+                .trim_end_matches(['\n', '\r'])
+                .to_string();
+
+            spliced.push(SplicedLines {
+                file: file.clone(),
+                first_line,
+                text,
+                highlights,
+                original,
+                deletions,
+            });
+        }
+        Some(spliced)
+    }
+}
+
+#[cfg(test)]
+mod suggestion_tests {
+    use crate::source::SourceMap;
+
+    use super::{CodeSuggestion, Substitution, Applicability};
+
+    fn suggestion(edits: &[(u32, u32, &str)]) -> CodeSuggestion {
+        CodeSuggestion {
+            message: String::new(),
+            substitutions: edits
+                .iter()
+                .map(|&(start, end, replacement)| Substitution {
+                    // Position 1 is the start of the first file in the source
+                    // map.
+                    span: (start + 1..end + 1).into(),
+                    replacement: replacement.to_string(),
+                })
+                .collect(),
+            applicability: Applicability::MachineApplicable,
+        }
+    }
+
+    #[test]
+    fn test_splice_lines() {
+        let src = "val x = 1;;\nval y = f 2\nval z = 3\n";
+        let source_map = SourceMap::from_string(src);
+
+        let spliced = suggestion(&[(9, 11, ""), (20, 23, "(f 2)")])
+            .splice_lines(&source_map)
+            .unwrap();
+        assert_eq!(spliced.len(), 1);
+        assert_eq!(spliced[0].first_line, 0);
+        assert_eq!(spliced[0].text, "val x = 1\nval y = (f 2)");
+        assert_eq!(spliced[0].highlights, vec![18..23]);
+        assert_eq!(spliced[0].original, "val x = 1;;\nval y = f 2");
+        assert_eq!(spliced[0].deletions, vec![9..11]);
+
+        // Overlapping substitutions cannot be applied together.
+        assert!(suggestion(&[(0, 5, "fun"), (4, 6, "y")]).splice_lines(&source_map).is_none());
+    }
+}
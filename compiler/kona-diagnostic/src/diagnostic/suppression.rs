@@ -0,0 +1,117 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use crate::source::Span;
+
+use super::{Diagnostic, Level, LintLevel, LintLevels, WARNINGS};
+
+/// Suppresses a lint in a range of the source code, e.g. the declaration
+/// after a `(*@allow unused-binding*)` comment.
+///
+/// Suppressions are added to the engine by
+/// [`DiagnosticEngine::add_suppression`], they act like `-A` for the lint in
+/// their span. Forbidden lints can't be suppressed.
+///
+/// [`DiagnosticEngine::add_suppression`]: super::DiagnosticEngine::add_suppression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suppression {
+    /// The name of the suppressed lint, or [`WARNINGS`] for all warnings.
+    pub lint: String,
+
+    /// The range where the lint is suppressed.
+    pub span: Span,
+
+    /// Where the suppression is written, e.g. the span of the comment. It's
+    /// used to report unused suppressions.
+    pub source: Span,
+}
+
+/// The suppressions of a [`DiagnosticEngine`](super::DiagnosticEngine) and
+/// whether they have matched a diagnostic.
+#[derive(Debug, Default)]
+pub(crate) struct Suppressions {
+    suppressions: Vec<(Suppression, bool)>,
+}
+
+impl Suppressions {
+    pub(crate) fn add(&mut self, suppression: Suppression) {
+        self.suppressions.push((suppression, false));
+    }
+
+    /// Returns `true` if the diagnostic is suppressed, and marks the matching
+    /// suppressions as used.
+    pub(crate) fn suppress(&mut self, diag: &Diagnostic, lint_levels: &LintLevels) -> bool {
+        let name = match diag.lint {
+            Some(lint) if lint_levels.level(lint) == LintLevel::Forbid => return false,
+            Some(lint) => Some(lint.name),
+            None if diag.level == Level::Warn => None,
+            None => return false,
+        };
+
+        let span = diag.span();
+        if span.is_dummy() {
+            return false;
+        }
+
+        let mut suppressed = false;
+        for (suppression, used) in &mut self.suppressions {
+            let matches_lint = suppression.lint == WARNINGS
+                || Some(suppression.lint.as_str()) == name;
+            if matches_lint && suppression.span.cross_over(span) {
+                *used = true;
+                suppressed = true;
+            }
+        }
+        suppressed
+    }
+
+    /// Returns the suppressions that have never matched.
+    pub(crate) fn unused(&self) -> Vec<Suppression> {
+        self.suppressions
+            .iter()
+            .filter(|(_, used)| !used)
+            .map(|(suppression, _)| suppression.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod suppression_tests {
+    use crate::diagnostic::{DiagnosticEngine, BufferEmitter, Lint, LintLevel};
+
+    use super::Suppression;
+
+    static UNUSED_BINDING: Lint = Lint {
+        name: "unused-binding",
+        default_level: LintLevel::Warn,
+        description: "detects bindings that are never used",
+    };
+
+    #[test]
+    fn test_suppressions() {
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+        engine.register_lints(&[&UNUSED_BINDING]);
+        engine.add_suppression(Suppression {
+            lint: "unused-binding".to_string(),
+            span: (10..20u32).into(),
+            source: (1..5u32).into(),
+        });
+        engine.add_suppression(Suppression {
+            lint: "unused-binding".to_string(),
+            span: (30..40u32).into(),
+            source: (21..25u32).into(),
+        });
+
+        engine.create_lint(&UNUSED_BINDING, "unused binding `x`").set_primary_span(12..13u32).emit();
+        engine.create_lint(&UNUSED_BINDING, "unused binding `y`").set_primary_span(22..23u32).emit();
+        engine.create_warn("not a lint").set_primary_span(12..13u32).emit();
+
+        let diagnostics = engine.drain_diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "unused binding `y`");
+
+        let unused = engine.unused_suppressions();
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].span, (30..40u32).into());
+    }
+}
@@ -6,15 +6,15 @@
 //! This diagnostic system consists of two parts:
 //!
 //! - A **source text management system** (in the [`source`] module). It provides a
-//!     mechanism to analyze and cache position information of source text.
-//!     After loading the source files into [`SourceMap`], you can query the
-//!     information like the file name, line number, column number, etc. of a
-//!     specified position [`Pos`] that is represented by [`u32`];
-//! - A **diagnostic engine** (inthe  [`diagnostic`] module) generates rich
-//!     diagnostic messages. A diagnostic [`Diagnostic`] consists of some
-//!     positions and message strings. Diagnostic engines can emit it to
-//!     different formats, print to console, write to file, or export it as
-//!     JSON.
+//!   mechanism to analyze and cache position information of source text.
+//!   After loading the source files into [`SourceMap`], you can query the
+//!   information like the file name, line number, column number, etc. of a
+//!   specified position [`Pos`] that is represented by [`u32`];
+//! - A **diagnostic engine** (in the [`diagnostic`] module) generates rich
+//!   diagnostic messages. A diagnostic [`Diagnostic`] consists of some
+//!   positions and message strings. Diagnostic engines can emit it to
+//!   different formats, print to console, write to file, or export it as
+//!   JSON.
 //!
 //! Most instances of the diagnostic engine require a source map to output more
 //! precise error messages.
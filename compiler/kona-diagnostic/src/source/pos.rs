@@ -129,12 +129,12 @@ impl Sub<u32> for Pos {
 impl SubAssign<u32> for Pos {
     #[inline(always)]
     fn sub_assign(&mut self, offset: u32) {
-        self.index -= offset as u32;
+        self.index -= offset;
     }
 }
 
 impl Default for Pos {
     fn default() -> Pos {
-        Pos { index: 0 }
+        Pos::dummy()
     }
 }
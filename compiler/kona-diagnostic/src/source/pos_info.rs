@@ -22,10 +22,15 @@ pub struct PosInfo {
     /// The 1-based line number.
     line: usize,
 
-    /// The 0-based column offset.
+    /// The 0-based column offset, in characters (Unicode scalar values).
     col: usize,
 
-    /// The 0-based column offset when displayed.
+    /// The 0-based column offset when displayed, in terminal columns.
+    ///
+    /// It's measured over extended grapheme clusters: the first character of
+    /// a cluster takes the width of the whole cluster, the others take none.
+    /// So a position inside a cluster, e.g. at a combining mark, is displayed
+    /// right after the cluster. Tabs advance to the next tab stop.
     col_display: usize,
 }
 
@@ -1,8 +1,9 @@
 // Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
 // root for license information.
 
-use std::{path::PathBuf, rc::Rc, io, fs};
+use std::{path::{PathBuf, Path}, rc::Rc, io, fs};
 
+use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthChar;
 
 use super::{Span, Pos, SourcePath};
@@ -37,8 +38,16 @@ pub struct SourceFile {
     /// This property may be used when printing source code and error messages
     /// in the terminal. See also Unicode Standard Annex #11 [East Asian Width].
     ///
+    /// Widths are measured over extended grapheme clusters, see
+    /// [`grapheme_width`]. The first character of a cluster takes the width of
+    /// the whole cluster, the others are zero-width.
+    ///
     /// [East Asian Width]: https://www.unicode.org/reports/tr11/
     non_narrow_chars: Vec<NonNarrowChar>,
+
+    /// Caches the position of invisible characters in the source file, see
+    /// [`InvisibleChar`].
+    invisible_chars: Vec<InvisibleChar>,
 }
 
 impl SourceFile {
@@ -66,7 +75,7 @@ impl SourceFile {
     /// Creates a new source file from the given path and source code.
     fn new(path: SourcePath, src: Rc<String>, start_pos: Pos) -> SourceFile {
         let end_pos = start_pos + src.len();
-        let (lines, multi_byte_chars, non_narrow_chars) =
+        let (lines, multi_byte_chars, non_narrow_chars, invisible_chars) =
             SourceFile::analyze(&src, start_pos);
         SourceFile {
             src,
@@ -75,6 +84,7 @@ impl SourceFile {
             lines,
             multi_byte_chars,
             non_narrow_chars,
+            invisible_chars,
         }
     }
 
@@ -84,6 +94,11 @@ impl SourceFile {
         self.path.readable_name()
     }
 
+    /// Returns the canonical path of the source file, if it is a local file.
+    pub fn local_path(&self) -> Option<&Path> {
+        self.path.local_path()
+    }
+
     pub fn src(&self) -> Rc<String> {
         self.src.clone()
     }
@@ -100,6 +115,19 @@ impl SourceFile {
         self.span.end()
     }
 
+    /// Returns `true` if both are the same file of a source map. Files in a
+    /// source map take disjoint position ranges, so a file is identified by
+    /// its span, even if it's loaded twice into different `Rc`s.
+    pub fn is_same_file(&self, other: &SourceFile) -> bool {
+        self.span == other.span
+    }
+
+    /// Returns the invisible characters in the source file, sorted by
+    /// position.
+    pub fn invisible_chars(&self) -> &[InvisibleChar] {
+        &self.invisible_chars
+    }
+
     pub fn is_local_file(&self) -> bool {
         self.path.is_local_file()
     }
@@ -187,8 +215,10 @@ impl SourceFile {
         }
     }
 
+    /// Looks up the line, column, and display column for a given [`Pos`].
+    /// Tabs advance the display column to the next multiple of `tab_width`.
     pub(crate) fn lookup_line_col_and_col_display(
-        &self, pos: Pos
+        &self, pos: Pos, tab_width: usize,
     ) -> (usize, usize, usize) {
         let (line, col) = self.lookup_line_and_col(pos);
         let col_display = {
@@ -201,15 +231,40 @@ impl SourceFile {
                 .non_narrow_chars[start_idx..]
                 .iter()
                 .take_while(|x| x.pos() < pos);
-            let width = non_narrow.clone()
-                .map(|x| x.width())
-                .sum::<usize>();
-            let count = non_narrow.count();
-            col + width - count
+            let start_idx = self
+                .multi_byte_chars
+                .binary_search_by_key(&linebpos, |x| x.pos())
+                .unwrap_or_else(|x| x);
+            let mut multi_byte = self.multi_byte_chars[start_idx..].iter().peekable();
+
+            // Narrow characters between the non-narrow ones take one column
+            // each, `next_col` is the column right after the last non-narrow
+            // character. The columns of the non-narrow characters are counted
+            // along the way, by skipping the extra bytes of the multi-byte
+            // characters before them.
+            let mut col_display = 0;
+            let mut next_col = 0;
+            let mut extra_byte = 0;
+            for x in non_narrow {
+                while let Some(mb) = multi_byte.next_if(|mb| mb.pos() < x.pos()) {
+                    extra_byte += mb.len() as usize - 1;
+                }
+                let x_col = x.pos().to_usize() - linebpos.to_usize() - extra_byte;
+                col_display += x_col - next_col;
+                col_display += x.width(col_display, tab_width);
+                next_col = x_col + 1;
+            }
+            col_display + col - next_col
         };
         (line, col, col_display)
     }
 
+    /// Returns the number of lines in the source file.
+    #[inline]
+    pub fn count_lines(&self) -> usize {
+        self.lines.len()
+    }
+
     #[inline]
     pub fn contains_pos(&self, pos: Pos) -> bool {
        self.span.contains(pos)
@@ -222,60 +277,68 @@ impl SourceFile {
 }
 
 impl SourceFile {
-    /// Finds all newlines, multi-byte characters, and non-narrow characters in a
-    /// source file.
+    /// Finds all newlines, multi-byte characters, non-narrow characters, and
+    /// invisible characters in a source file.
     fn analyze(
         src: &str,
         start_pos: Pos,
-    ) -> (Vec<Pos>, Vec<MultiByteChar>, Vec<NonNarrowChar>) {
+    ) -> (Vec<Pos>, Vec<MultiByteChar>, Vec<NonNarrowChar>, Vec<InvisibleChar>) {
         let mut lines = vec![start_pos];
         let mut multi_byte_chars = vec![];
         let mut non_narrow_chars = vec![];
+        let mut invisible_chars = vec![];
 
         let offset = start_pos.to_usize();
 
-        let mut idx = 0;
         let src_bytes = src.as_bytes();
 
-        while idx < src_bytes.len() {
-            let byte = src_bytes[idx];
-
-            // How much to advance in order to get to the next UTF-8 char in the
-            // string.
-            let mut char_len = 1;
-
-            if byte < 32 {
-                // This is an ASCII control character.
-                let pos = Pos::from_usize(idx + offset);
-
-                match byte {
-                    b'\n' => lines.push(pos + 1u32),
-                    b'\r' if src_bytes.get(idx + 1) != Some(&b'\n') => {
-                        lines.push(pos + 1u32);
-                    },
-                    b'\t' => non_narrow_chars.push(NonNarrowChar::new(pos, 4)),
-                    _ => non_narrow_chars.push(NonNarrowChar::new(pos, 0)),
+        for (idx, cluster) in src.grapheme_indices(true) {
+            if cluster.is_ascii() {
+                // Most clusters are a single ASCII character, except CRLF.
+                for idx in idx..idx + cluster.len() {
+                    let pos = Pos::from_usize(idx + offset);
+                    match src_bytes[idx] {
+                        b'\n' => lines.push(pos + 1u32),
+                        b'\r' if src_bytes.get(idx + 1) != Some(&b'\n') => {
+                            lines.push(pos + 1u32);
+                        },
+                        b'\t' => non_narrow_chars.push(NonNarrowChar::tab(pos)),
+                        // Other ASCII control characters and "DEL".
+                        0..=31 | 127 => non_narrow_chars.push(NonNarrowChar::new(pos, 0)),
+                        _ => {}
+                    }
                 }
-            } else if byte >= 127 {
-                // This is either ASCII control character "DEL" or the beginning of
-                // a multibyte char. Just decode to `char`.
-                let char = (&src[idx..]).chars().next().unwrap();
-                char_len = char.len_utf8();
+                continue;
+            }
 
+            // Invisible characters are always a cluster of their own, they are
+            // displayed escaped.
+            if let Some(ch) = cluster.chars().next().filter(|&ch| is_invisible_char(ch)) {
                 let pos = Pos::from_usize(idx + offset);
+                let invisible = InvisibleChar { pos, ch };
+                multi_byte_chars.push(MultiByteChar::new(pos, ch.len_utf8() as u8));
+                non_narrow_chars.push(NonNarrowChar::invisible(pos, invisible.escaped().len()));
+                invisible_chars.push(invisible);
+                continue;
+            }
+
+            // The first character takes the width of the whole cluster, the
+            // others are zero-width. It may be an ASCII character followed by
+            // combining marks.
+            let width = grapheme_width(cluster);
+            for (char_idx, char) in cluster.char_indices() {
+                let pos = Pos::from_usize(idx + char_idx + offset);
+                let char_len = char.len_utf8();
 
                 if char_len > 1 {
                     multi_byte_chars.push(MultiByteChar::new(pos, char_len as u8));
                 }
 
-                let char_width = UnicodeWidthChar::width(char).unwrap_or(0);
-
+                let char_width = if char_idx == 0 { width } else { 0 };
                 if char_width != 1 {
                     non_narrow_chars.push(NonNarrowChar::new(pos, char_width));
                 }
             }
-
-            idx += char_len;
         }
 
         // The code above optimistically registers a new line after each newline
@@ -288,8 +351,72 @@ impl SourceFile {
             }
         }
 
-        (lines, multi_byte_chars, non_narrow_chars)
+        (lines, multi_byte_chars, non_narrow_chars, invisible_chars)
+    }
+}
+
+/// A character that is not visible but may change how the source code is
+/// displayed, e.g. a bidirectional override that reorders the text around it
+/// (see [Trojan Source]). They are displayed escaped, e.g. `<U+202E>`.
+///
+/// [Trojan Source]: https://trojansource.codes/
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvisibleChar {
+    pub pos: Pos,
+    pub ch: char,
+}
+
+impl InvisibleChar {
+    /// Returns `true` if it's a bidirectional embedding, override or isolate
+    /// control.
+    pub fn is_bidi_control(&self) -> bool {
+        matches!(self.ch, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+    }
+
+    /// Returns the escaped form of this character, e.g. `<U+202E>`.
+    pub fn escaped(&self) -> String {
+        escape_invisible_char(self.ch)
+    }
+}
+
+pub(crate) fn escape_invisible_char(ch: char) -> String {
+    format!("<U+{:04X}>", ch as u32)
+}
+
+/// Returns `true` for the bidirectional controls and the invisible characters
+/// that are displayed escaped. Zero-width joiners are not included, they are
+/// part of emoji sequences and some scripts.
+pub(crate) fn is_invisible_char(ch: char) -> bool {
+    matches!(ch,
+        // Bidirectional embeddings, overrides and isolates.
+        '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}'
+        // Bidirectional marks.
+        | '\u{200E}' | '\u{200F}' | '\u{061C}'
+        // Zero width space, word joiner, zero width no-break space and soft
+        // hyphen.
+        | '\u{200B}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}')
+}
+
+/// Returns the display width of an extended grapheme cluster, which is how
+/// most terminals draw it:
+///
+/// - The width of its widest character, e.g. a combining mark adds nothing to
+///   its base character, and an emoji ZWJ sequence is as wide as one emoji;
+/// - 2 for an emoji presentation sequence (with U+FE0F) or a flag (a pair of
+///   regional indicators);
+/// - At most 2.
+///
+/// Tabs are not handled here, they depend on the tab stops.
+pub(crate) fn grapheme_width(cluster: &str) -> usize {
+    let is_regional_indicator = |ch: &char| ('\u{1F1E6}'..='\u{1F1FF}').contains(ch);
+    if cluster.contains('\u{FE0F}') || cluster.chars().filter(is_regional_indicator).count() >= 2 {
+        return 2;
     }
+    cluster.chars()
+        .map(|ch| UnicodeWidthChar::width(ch).unwrap_or(0))
+        .max()
+        .unwrap_or(0)
+        .min(2)
 }
 
 /// Represents a multi-byte UTF-8 unicode scalar in the source code.
@@ -337,8 +464,11 @@ enum NonNarrowCharKind {
     /// A full-width character.
     Wide,
 
-    /// A tab, treated as four spaces.
+    /// A tab, which advances to the next tab stop.
     Tab,
+
+    /// An invisible character, displayed escaped with the given width.
+    Invisible(usize),
 }
 
 impl NonNarrowChar {
@@ -347,24 +477,65 @@ impl NonNarrowChar {
     fn new(pos: Pos, width: usize) -> Self {
         let kind = match width {
             0 => NonNarrowCharKind::ZeroWidth,
-            2 => NonNarrowCharKind::Wide,
-            _ => NonNarrowCharKind::Tab,
+            _ => NonNarrowCharKind::Wide,
         };
         NonNarrowChar { pos, kind }
     }
 
+    /// Creates a new [`NonNarrowChar`] for an invisible character.
+    fn invisible(pos: Pos, width: usize) -> Self {
+        NonNarrowChar { pos, kind: NonNarrowCharKind::Invisible(width) }
+    }
+
+    /// Creates a new [`NonNarrowChar`] for a tab.
+    fn tab(pos: Pos) -> Self {
+        NonNarrowChar { pos, kind: NonNarrowCharKind::Tab }
+    }
+
     /// Returns the position of this character.
     #[inline]
     fn pos(&self) -> Pos {
         self.pos
     }
 
-    /// Returns the width of this character.
-    fn width(&self) -> usize {
+    /// Returns the width of this character when displayed at `col_display`.
+    fn width(&self, col_display: usize, tab_width: usize) -> usize {
         match self.kind {
             NonNarrowCharKind::ZeroWidth => 0,
             NonNarrowCharKind::Wide => 2,
-            NonNarrowCharKind::Tab => 4,
+            NonNarrowCharKind::Tab => tab_width - col_display % tab_width,
+            NonNarrowCharKind::Invisible(width) => width,
         }
     }
 }
+
+#[cfg(test)]
+mod source_file_tests {
+    use std::rc::Rc;
+
+    use crate::source::Pos;
+
+    use super::SourceFile;
+
+    #[test]
+    fn test_lookup_col_display() {
+        // "é" is 2 bytes and 1 column, "名" is 3 bytes and 2 columns, U+200B is
+        // displayed escaped as `<U+200B>`.
+        let src = "x\té名\tb\u{200B}c\nnext\n";
+        // Position 0 is reserved for dummy spans.
+        let file = SourceFile::test_file(Rc::new(src.to_string()), None, 0, Pos::from_usize(1));
+        let cols = src[..src.find('\n').unwrap()]
+            .char_indices()
+            .map(|(idx, _)| {
+                let (line, col, col_display) =
+                    file.lookup_line_col_and_col_display(Pos::from_usize(idx + 1), 4);
+                assert_eq!(line, 1);
+                (col, col_display)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(cols, [(0, 0), (1, 1), (2, 4), (3, 5), (4, 7), (5, 8), (6, 9), (7, 17)]);
+
+        let next = src.find("next").unwrap();
+        assert_eq!(file.lookup_line_col_and_col_display(Pos::from_usize(next + 3), 4), (2, 2, 2));
+    }
+}
@@ -3,7 +3,7 @@
 
 use std::{rc::Rc, collections::HashMap, path::PathBuf, io, fs, sync::{RwLock, atomic::{AtomicUsize, Ordering, AtomicU32}}};
 
-use crate::source::SourceFile;
+use crate::source::{SourceFile, InvisibleChar};
 
 use super::{Pos, SourcePath, Span, PosInfo, SourceLine};
 
@@ -31,8 +31,14 @@ pub struct SourceMap {
     // only function that can increase `used_pos_space`.
 
     source_files: RwLock<SourceMapFiles>,
+
+    /// The distance between two tab stops, used to calculate display columns.
+    tab_width: AtomicUsize,
 }
 
+/// The default distance between two tab stops.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
 impl SourceMap {
     pub fn new() -> Self {
         SourceMap {
@@ -40,9 +46,23 @@ impl SourceMap {
             used_pos_space: AtomicUsize::new(1),
             used_virtual_file_number: AtomicU32::new(0),
             source_files: RwLock::new(SourceMapFiles::default()),
+            tab_width: AtomicUsize::new(DEFAULT_TAB_WIDTH),
         }
     }
 
+    /// Returns the distance between two tab stops, see [`set_tab_width`].
+    ///
+    /// [`set_tab_width`]: SourceMap::set_tab_width
+    pub fn tab_width(&self) -> usize {
+        self.tab_width.load(Ordering::Relaxed)
+    }
+
+    /// Sets the distance between two tab stops, `DEFAULT_TAB_WIDTH` by default.
+    /// A tab advances the display column to the next tab stop.
+    pub fn set_tab_width(&self, tab_width: usize) {
+        self.tab_width.store(tab_width.max(1), Ordering::Relaxed);
+    }
+
     fn allocate_pos_space(&self, size: usize) -> usize {
         let current = self.used_pos_space.load(Ordering::Relaxed);
 
@@ -59,7 +79,7 @@ impl SourceMap {
                 .compare_exchange(current, next, Ordering::Relaxed, Ordering::Relaxed)
                 .is_ok()
             {
-                return usize::try_from(current).unwrap();
+                return current;
             }
         }
     }
@@ -155,6 +175,12 @@ impl SourceMap {
     }
 }
 
+impl Default for SourceMap {
+    fn default() -> Self {
+        SourceMap::new()
+    }
+}
+
 pub type LookupResult<T> = Result<T, LookupError>;
 
 
@@ -187,8 +213,46 @@ impl SourceMap {
     }
 
     pub fn lookup_pos_info(&self, pos: Pos) -> LookupResult<PosInfo> {
+        self.lookup_pos_info_with_tab_width(pos, self.tab_width())
+    }
+
+    /// Same as `lookup_pos_info`, but calculates the display column with the
+    /// given tab width instead of the one of the source map.
+    pub fn lookup_pos_info_with_tab_width(&self, pos: Pos, tab_width: usize) -> LookupResult<PosInfo> {
         let file = self.lookup_file_at_pos(pos)?;
-        let (line, col, col_display) = file.lookup_line_col_and_col_display(pos);
+        let (line, col, col_display) =
+            file.lookup_line_col_and_col_display(pos, tab_width.max(1));
+        Ok(PosInfo::new(file, line, col, col_display))
+    }
+
+    /// Looks up the position information of the start of the span.
+    ///
+    /// Unlike `lookup_pos_info(span.start())`, this also works for zero-length
+    /// spans at the end of a file, e.g. of an "unexpected end of file" error,
+    /// which are resolved to the end of the last line.
+    pub fn lookup_start_pos_info(&self, span: Span) -> LookupResult<PosInfo> {
+        self.lookup_start_pos_info_with_tab_width(span, self.tab_width())
+    }
+
+    /// Same as `lookup_start_pos_info`, but calculates the display column with
+    /// the given tab width instead of the one of the source map.
+    pub fn lookup_start_pos_info_with_tab_width(
+        &self, span: Span, tab_width: usize,
+    ) -> LookupResult<PosInfo> {
+        let file = self.lookup_file_at_span(span)?;
+        let (line, col, col_display) =
+            file.lookup_line_col_and_col_display(span.start(), tab_width.max(1));
+        Ok(PosInfo::new(file, line, col, col_display))
+    }
+
+    /// Looks up the position information of the exclusive end of the span.
+    ///
+    /// Unlike `lookup_pos_info(span.end())`, this also works for spans ending
+    /// at the end of a file, where the end position is out of the file.
+    pub fn lookup_end_pos_info(&self, span: Span) -> LookupResult<PosInfo> {
+        let file = self.lookup_file_at_span(span)?;
+        let (line, col, col_display) =
+            file.lookup_line_col_and_col_display(span.end(), self.tab_width());
         Ok(PosInfo::new(file, line, col, col_display))
     }
 
@@ -229,14 +293,35 @@ impl SourceMap {
         }
     }
 
-    /// Finds the source file containing the given span.
+    /// Finds the source file containing the given position, or ending right
+    /// before it. The end of a file is not in the file, but it's where
+    /// zero-length spans at the end of the file are.
+    pub fn lookup_file_at_pos_or_end(&self, pos: Pos) -> LookupResult<Rc<SourceFile>> {
+        match self.lookup_file_at_pos(pos) {
+            Err(LookupError::OutOfRange) => {
+                // Files are one position apart, so the end of a file is never
+                // in the next one.
+                let files = self.source_files.read().unwrap();
+                let idx = files.files.partition_point(|file| file.start_pos() <= pos);
+                idx.checked_sub(1)
+                    .map(|idx| files.files[idx].clone())
+                    .filter(|file| file.end_pos() == pos)
+                    .ok_or(LookupError::OutOfRange)
+            }
+            result => result,
+        }
+    }
+
+    /// Finds the source file containing the given span. A zero-length span may
+    /// be at the end of the file.
     pub fn lookup_file_at_span(&self, span: Span) -> LookupResult<Rc<SourceFile>> {
+        if span.end() == span.start() {
+            return self.lookup_file_at_pos_or_end(span.start());
+        }
         let start_file = self.lookup_file_at_pos(span.start())?;
-        let end_file = self.lookup_file_at_pos(span.end() - 1u32)?;
+        let end_file = self.lookup_file_at_pos(last_pos(span))?;
 
-        // FIXME: For now we use the span to check the equality of two files.
-        // This is really a hacky way. Maybe we should implement file ID later.
-        if start_file.span() == end_file.span() {
+        if start_file.is_same_file(&end_file) {
             Ok(start_file)
         } else {
             Err(LookupError::SpanAcrossFiles)
@@ -250,21 +335,24 @@ impl SourceMap {
     }
 
     pub fn lookup_lines_at_span(&self, span: Span) -> LookupResult<Vec<SourceLine>> {
-        let start_file = self.lookup_file_at_pos(span.start())?;
-        let end_file = self.lookup_file_at_pos(span.end() - 1u32)?;
+        let file = self.lookup_file_at_span(span)?;
+        let start_line = file.lookup_line_at_pos(span.start()).unwrap();
+        let end_line = file.lookup_line_at_pos(last_pos(span)).unwrap();
 
-        // FIXME: For now we use the span to check the equality of two files.
-        // This is really a hacky way. Maybe we should implement file ID later.
-        if start_file.span() == end_file.span() {
-            let start_line = start_file.lookup_line_at_pos(span.start()).unwrap();
-            let end_line = end_file.lookup_line_at_pos(span.end() - 1u32).unwrap();
+        Ok((start_line..=end_line)
+            .map(|line| SourceLine::new(file.clone(), line as u32))
+            .collect())
+    }
 
-            Ok((start_line..=end_line)
-                .map(|line| SourceLine::new(start_file.clone(), line as u32))
-                .collect())
-        } else {
-            Err(LookupError::SpanAcrossFiles)
-        }
+    /// Finds the invisible characters in the given span, e.g. bidirectional
+    /// overrides, see [`InvisibleChar`].
+    pub fn lookup_invisible_chars(&self, span: Span) -> LookupResult<Vec<InvisibleChar>> {
+        let file = self.lookup_file_at_span(span)?;
+        Ok(file.invisible_chars()
+            .iter()
+            .filter(|invisible| span.contains(invisible.pos))
+            .copied()
+            .collect())
     }
 
     /// Returns the source file at the given span.
@@ -282,6 +370,17 @@ impl SourceMap {
     }
 }
 
+/// Returns the last position in the span, or its start if it's empty, e.g. a
+/// zero-length span at the beginning of a line is on that line, not the
+/// previous one.
+fn last_pos(span: Span) -> Pos {
+    if span.end() > span.start() {
+        span.end() - 1u32
+    } else {
+        span.start()
+    }
+}
+
 #[derive(Default)]
 struct SourceMapFiles {
     /// The source files.
@@ -1,7 +1,7 @@
 // Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
 // root for license information.
 
-use std::path::PathBuf;
+use std::path::{PathBuf, Path};
 
 /// Represents the path to a source file. Can only be created by [`SourceMap`].
 ///
@@ -36,6 +36,13 @@ impl SourcePath {
         matches!(self.kind, SourcePathKind::Test { .. })
     }
 
+    pub(super) fn local_path(&self) -> Option<&Path> {
+        match self.kind {
+            SourcePathKind::Local(ref path) => Some(path),
+            SourcePathKind::Test { .. } => None,
+        }
+    }
+
     pub(super) fn readable_name(&self) -> String {
         use path_helper::{clear_unc_prefix, diff_paths};
         match self.kind {
@@ -72,7 +79,9 @@ enum SourcePathKind {
 }
 
 mod path_helper {
-    use std::path::{PathBuf, Path, Component, Prefix};
+    use std::path::{PathBuf, Path, Component};
+    #[cfg(windows)]
+    use std::path::Prefix;
 
     /// Get relative path from base to the given path.
     pub fn diff_paths<P, B>(path: P, base: B) -> Option<PathBuf>
@@ -103,8 +112,8 @@ mod path_helper {
                     }
                     (None, _) => comps.push(Component::ParentDir),
                     (Some(a), Some(b)) if comps.is_empty() && a == b => (),
-                    (Some(a), Some(b)) if b == Component::CurDir => comps.push(a),
-                    (Some(_), Some(b)) if b == Component::ParentDir => return None,
+                    (Some(a), Some(Component::CurDir)) => comps.push(a),
+                    (Some(_), Some(Component::ParentDir)) => return None,
                     (Some(a), Some(_)) => {
                         comps.push(Component::ParentDir);
                         for _ in itb {
@@ -0,0 +1,240 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::{io::{self, Write}, path::{Path, PathBuf}, rc::Rc};
+
+use kona_diagnostic::{
+    source::{SourceMap, SourceFile},
+    diagnostic::{
+        DiagnosticEngine, Fixes, FatalError, TtyEmitter, ShortEmitter, Emitter, LintLevel,
+        WARNINGS, Baseline, ColorChoice,
+    },
+};
+
+use crate::{error_codes, invisible_chars, lints, suppressions, options::{Command, Options, ErrorFormat, USAGE}};
+
+/// Runs the compiler with the given options, returns the exit code.
+pub fn run(options: Options) -> i32 {
+    match options.command {
+        Command::Help => {
+            print_stdout(USAGE);
+            return 0;
+        }
+        Command::Explain { ref code } => return explain(code),
+        _ => {}
+    }
+
+    let session = Session::new(options.color, options.error_format);
+    let code = FatalError::catch(|| {
+        let (baseline, write_baseline) = match &options.command {
+            Command::Check { baseline, write_baseline } => {
+                (baseline.as_deref(), write_baseline.as_deref())
+            }
+            _ => (None, None),
+        };
+        if let Some(path) = baseline {
+            session.set_baseline(path);
+        }
+        if let Some(path) = write_baseline {
+            session.engine.record_baseline(Some(Baseline::root_of(path)));
+        }
+
+        session.set_lint_levels(&options.lint_levels);
+        let files = session.load_files(&options.files);
+        session.engine.abort_if_errors();
+        session.add_suppressions(&files);
+        session.check(&files);
+        session.report_unused_suppressions();
+
+        // Errors reported before or while fixing still make the run fail.
+        if let Command::Fix { dry_run } = options.command {
+            session.fix(dry_run);
+        }
+        if let Some(path) = write_baseline {
+            session.write_baseline(path);
+        }
+        session.engine.abort_if_errors();
+        0
+    });
+    session.engine.finish();
+
+    // A closed pipe only means nobody reads the diagnostics any more, other
+    // errors (e.g. a full disk) lose them, so the run fails.
+    match session.engine.take_emit_error() {
+        Some(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+            let _ = writeln!(io::stderr(), "error: couldn't write the diagnostics: {}", err);
+            1
+        }
+        _ => code.unwrap_or(1),
+    }
+}
+
+/// Prints to stdout. Unlike `print!`, it doesn't panic if stdout is closed,
+/// e.g. `kona fix --dry-run | head` just stops printing.
+fn print_stdout(text: &str) {
+    let _ = io::stdout().write_all(text.as_bytes());
+}
+
+/// Prints the explanation of an error code.
+fn explain(code: &str) -> i32 {
+    match error_codes::registry().find_explanation(code) {
+        Some(explanation) => {
+            print_stdout(explanation);
+            0
+        }
+        None => {
+            DiagnosticEngine::with_emitter(Box::new(TtyEmitter::no_source_map()))
+                .create_err(format!("no extended information for `{}`", code))
+                .emit();
+            1
+        }
+    }
+}
+
+/// The state of a compiler run.
+struct Session {
+    source_map: Rc<SourceMap>,
+    engine: DiagnosticEngine,
+}
+
+impl Session {
+    fn new(color: ColorChoice, error_format: ErrorFormat) -> Session {
+        let source_map = Rc::new(SourceMap::new());
+        let emitter: Box<dyn Emitter> = match error_format {
+            ErrorFormat::Human => Box::new(TtyEmitter::stderr(Some(source_map.clone()), color)),
+            ErrorFormat::Short => Box::new(ShortEmitter::stderr(Some(source_map.clone()))),
+        };
+        let engine = DiagnosticEngine::with_emitter(emitter)
+            .with_registry(error_codes::registry());
+        engine.register_lints(lints::LINTS);
+        Session { source_map, engine }
+    }
+
+    /// Sets the lint levels from the command line, warns about unknown lints.
+    fn set_lint_levels(&self, levels: &[(String, LintLevel)]) {
+        for (name, level) in levels {
+            if name != WARNINGS && self.engine.find_lint(name).is_none() {
+                self.engine
+                    .create_warn(format!("unknown lint `{}`", name))
+                    .set_code("W0002")
+                    .emit();
+                continue;
+            }
+            self.engine.set_lint_level(name, *level);
+        }
+    }
+
+    /// Loads the source files into the source map. Reports an error for each
+    /// file that cannot be read.
+    fn load_files(&self, paths: &[PathBuf]) -> Vec<Rc<SourceFile>> {
+        let mut files = vec![];
+        for path in paths {
+            match self.source_map.load_local_file(path.clone()) {
+                Ok(file) => files.push(file),
+                Err(err) => {
+                    self.engine
+                        .create_err(format!("couldn't read `{}`: {}", path.display(), err))
+                        .set_code("E0001")
+                        .emit();
+                }
+            }
+        }
+        files
+    }
+
+    /// Adds the lint suppressions written as comments in the source files,
+    /// see [`suppressions`].
+    fn add_suppressions(&self, files: &[Rc<SourceFile>]) {
+        for file in files {
+            for suppression in suppressions::scan_suppressions(file) {
+                if suppression.lint != WARNINGS && self.engine.find_lint(&suppression.lint).is_none() {
+                    self.engine
+                        .create_warn(format!("unknown lint `{}`", suppression.lint))
+                        .set_code("W0002")
+                        .set_primary_span(suppression.source)
+                        .emit();
+                    continue;
+                }
+                self.engine.add_suppression(suppression);
+            }
+        }
+    }
+
+    /// Warns about the suppressions that have never suppressed a diagnostic.
+    fn report_unused_suppressions(&self) {
+        for suppression in self.engine.unused_suppressions() {
+            self.engine
+                .create_lint(&lints::UNUSED_SUPPRESSION,
+                    format!("unused suppression of `{}`", suppression.lint))
+                .set_primary_label(suppression.source, "nothing is suppressed here")
+                .emit();
+        }
+    }
+
+    /// Hides the warnings recorded in the baseline file.
+    fn set_baseline(&self, path: &Path) {
+        match Baseline::load(path) {
+            Ok(baseline) => self.engine.set_baseline(baseline),
+            Err(err) => {
+                self.engine
+                    .create_err(format!("couldn't read baseline `{}`: {}", path.display(), err))
+                    .set_code("E0003")
+                    .emit();
+                self.engine.abort_if_errors();
+            }
+        }
+    }
+
+    /// Writes the warnings emitted so far to the baseline file.
+    fn write_baseline(&self, path: &Path) {
+        if let Err(err) = self.engine.take_recorded_baseline().write(path) {
+            self.engine
+                .create_err(format!("couldn't write baseline `{}`: {}", path.display(), err))
+                .set_code("E0004")
+                .emit();
+        }
+    }
+
+    /// Runs all checks on the loaded source files.
+    fn check(&self, files: &[Rc<SourceFile>]) {
+        for file in files {
+            invisible_chars::check_invisible_chars(&self.engine, file);
+        }
+
+        // TODO: The front end is not there yet, passes and lints should be
+        // called from here once they are implemented.
+    }
+
+    /// Applies the machine-applicable suggestions emitted so far, or prints
+    /// them as a unified diff in dry-run mode.
+    fn fix(&self, dry_run: bool) {
+        let fixes = Fixes::new(
+            &self.source_map,
+            self.engine.take_applicable_suggestions(),
+        );
+
+        if !fixes.skipped().is_empty() {
+            self.engine
+                .create_warn(format!(
+                    "skipped {} suggestion(s) that couldn't be applied",
+                    fixes.skipped().len()))
+                .set_code("W0001")
+                .emit();
+        }
+
+        if dry_run {
+            print_stdout(&fixes.unified_diff());
+            return;
+        }
+
+        for fix in fixes.files() {
+            if let Err(err) = fix.apply() {
+                self.engine
+                    .create_err(format!("couldn't write `{}`: {}", fix.file().name(), err))
+                    .set_code("E0002")
+                    .emit();
+                return;
+            }
+        }
+    }
+}
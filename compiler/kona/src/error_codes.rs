@@ -0,0 +1,71 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+//! Error codes of the compiler and their explanations, printed by
+//! `kona --explain`.
+//!
+//! To add a code, write its explanation to `error_codes/<CODE>.md` and list it
+//! in `register_error_codes!` below. Codes are never reused.
+
+use kona_diagnostic::diagnostic::Registry;
+
+macro_rules! register_error_codes {
+    ($($code:ident),* $(,)?) => {
+        /// All error codes with their explanations.
+        pub const ERROR_CODES: &[(&str, &str)] = &[
+            $((stringify!($code), include_str!(concat!("error_codes/", stringify!($code), ".md"))),)*
+        ];
+    };
+}
+
+register_error_codes! {
+    E0001,
+    E0002,
+    E0003,
+    E0004,
+    W0001,
+    W0002,
+}
+
+pub fn registry() -> Registry {
+    Registry::new(ERROR_CODES)
+}
+
+#[cfg(test)]
+mod error_codes_tests {
+    use super::ERROR_CODES;
+
+    #[test]
+    fn test_error_codes() {
+        for (idx, (code, explanation)) in ERROR_CODES.iter().enumerate() {
+            let (kind, number) = code.split_at(1);
+            assert!(matches!(kind, "E" | "W") && number.len() == 4
+                && number.chars().all(|c| c.is_ascii_digit()),
+                "malformed error code `{}`", code);
+            assert!(!explanation.trim().is_empty(), "`{}` has no explanation", code);
+            assert!(!ERROR_CODES[..idx].iter().any(|(other, _)| other == code),
+                "`{}` is registered twice", code);
+        }
+    }
+
+    #[test]
+    fn test_emitted_codes_are_registered() {
+        let src_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut num_codes = 0;
+        for entry in std::fs::read_dir(src_dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "rs") {
+                continue;
+            }
+            let src = std::fs::read_to_string(&path).unwrap();
+            for (idx, _) in src.match_indices(".set_code(\"") {
+                let rest = &src[idx + ".set_code(\"".len()..];
+                let code = &rest[..rest.find('"').unwrap()];
+                assert!(ERROR_CODES.iter().any(|(other, _)| *other == code),
+                    "`{}` in `{}` is not registered", code, path.display());
+                num_codes += 1;
+            }
+        }
+        assert!(num_codes > 0);
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+//! The `invisible-character` lint.
+//!
+//! Bidirectional overrides in comments and string literals can make the code
+//! look different from how the compiler reads it, see [Trojan Source]. Other
+//! invisible characters, e.g. zero width spaces, hide in them as well.
+//!
+//! [Trojan Source]: https://trojansource.codes/
+
+use std::rc::Rc;
+
+use kona_diagnostic::{
+    source::{SourceFile, Span},
+    diagnostic::{DiagnosticEngine, Applicability},
+};
+
+use crate::{lints, tokens::{tokenize, TokenKind}};
+
+/// Reports the invisible characters in the comments and string literals of a
+/// source file.
+pub fn check_invisible_chars(engine: &DiagnosticEngine, file: &Rc<SourceFile>) {
+    let invisible_chars = file.invisible_chars();
+    if invisible_chars.is_empty() {
+        return;
+    }
+
+    let src = file.src();
+    for token in tokenize(&src) {
+        let (start, end) = (file.start_pos() + token.start, file.start_pos() + token.end);
+        let in_string = match token.kind {
+            TokenKind::Comment => false,
+            TokenKind::String => true,
+            _ => continue,
+        };
+
+        for invisible in invisible_chars.iter().filter(|ch| start <= ch.pos && ch.pos < end) {
+            let span = Span::new(invisible.pos, invisible.pos + invisible.ch.len_utf8());
+            let code_point = format!("U+{:04X}", invisible.ch as u32);
+            let (what, label) = if invisible.is_bidi_control() {
+                ("bidirectional control character", "this character changes the direction of the text")
+            } else {
+                ("invisible character", "this character is invisible")
+            };
+
+            let diag = engine
+                .create_lint(&lints::INVISIBLE_CHARACTER, format!("{} `{}` in a {}",
+                    what, code_point, if in_string { "string literal" } else { "comment" }))
+                .set_primary_label(span, label);
+            let diag = if invisible.is_bidi_control() {
+                diag.add_note("it may make the code look different from how the compiler reads it")
+            } else {
+                diag
+            };
+            let diag = if in_string {
+                diag.add_suggestion(span, "if the character is intended, use an escape",
+                    format!("\\u{:04X}", invisible.ch as u32), Applicability::MachineApplicable)
+            } else {
+                diag.add_suggestion(span, "remove the character", "",
+                    Applicability::MachineApplicable)
+            };
+            diag.emit();
+        }
+    }
+}
+
+#[cfg(test)]
+mod invisible_chars_tests {
+    use kona_diagnostic::{
+        source::SourceMap,
+        diagnostic::{DiagnosticEngine, BufferEmitter, Level},
+    };
+
+    use crate::lints;
+
+    use super::check_invisible_chars;
+
+    #[test]
+    fn test_check_invisible_chars() {
+        let src = "val access = \"user\u{202E} \u{2066}// admin\u{2069}\u{2066}\" (* check \u{200B}*)\nval x\u{200B} = 1\n";
+        let source_map = SourceMap::new();
+        let file = source_map.load_test_file(None, src.to_string());
+        let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+        engine.register_lints(lints::LINTS);
+        check_invisible_chars(&engine, &file);
+
+        let diagnostics = engine.drain_diagnostics();
+        let messages = diagnostics.iter().map(|diag| diag.message.as_str()).collect::<Vec<_>>();
+        assert_eq!(messages, [
+            "bidirectional control character `U+202E` in a string literal",
+            "bidirectional control character `U+2066` in a string literal",
+            "bidirectional control character `U+2069` in a string literal",
+            "bidirectional control character `U+2066` in a string literal",
+            "invisible character `U+200B` in a comment",
+        ]);
+        assert!(diagnostics.iter().all(|diag| diag.level == Level::Error));
+        assert_eq!(diagnostics[0].suggestions[0].substitutions[0].replacement, "\\u202E");
+    }
+}
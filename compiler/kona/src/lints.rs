@@ -0,0 +1,36 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+//! Lints of the compiler, their levels can be set with `-A`, `-W`, `-D` and
+//! `-F` on the command line.
+
+use kona_diagnostic::diagnostic::{Lint, LintLevel};
+
+/// Bindings that are never used.
+pub static UNUSED_BINDING: Lint = Lint {
+    name: "unused-binding",
+    default_level: LintLevel::Warn,
+    description: "detects bindings that are never used",
+};
+
+/// `(*@allow ...*)` comments that never suppress anything.
+pub static UNUSED_SUPPRESSION: Lint = Lint {
+    name: "unused-suppression",
+    default_level: LintLevel::Warn,
+    description: "detects suppression comments that never suppress a diagnostic",
+};
+
+/// Invisible characters in comments and string literals, see
+/// [`invisible_chars`](crate::invisible_chars).
+pub static INVISIBLE_CHARACTER: Lint = Lint {
+    name: "invisible-character",
+    default_level: LintLevel::Deny,
+    description: "detects bidirectional overrides and other invisible characters in comments and strings",
+};
+
+/// All lints of the compiler.
+pub static LINTS: &[&Lint] = &[
+    &UNUSED_BINDING,
+    &UNUSED_SUPPRESSION,
+    &INVISIBLE_CHARACTER,
+];
@@ -1,6 +1,33 @@
 // Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
 // root for license information.
 
+mod driver;
+mod error_codes;
+mod invisible_chars;
+mod lints;
+mod options;
+mod suppressions;
+mod tokens;
+
+use std::process;
+
+use kona_diagnostic::diagnostic::{DiagnosticEngine, TtyEmitter};
+
+use options::Options;
+
 fn main() {
-    println!("Hello, world!");
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let code = match Options::parse(&args) {
+        Ok(options) => driver::run(options),
+        Err(msg) => {
+            DiagnosticEngine::with_emitter(Box::new(TtyEmitter::no_source_map()))
+                .create_err(msg)
+                .add_help("run `kona help` for more information")
+                .emit();
+            2
+        }
+    };
+
+    process::exit(code);
 }
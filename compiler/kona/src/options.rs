@@ -0,0 +1,260 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+use std::path::PathBuf;
+
+use kona_diagnostic::diagnostic::{LintLevel, ColorChoice};
+
+pub const USAGE: &str = "\
+Usage: kona <COMMAND> [OPTIONS] <FILE>...
+       kona --explain <CODE>
+
+Commands:
+    check        Check the source files and report diagnostics
+    fix          Apply machine-applicable suggestions to the source files
+    help         Print this message
+
+Options:
+    --dry-run    (fix) Print a unified diff instead of rewriting the files
+    --baseline <FILE>
+                 (check) Hide the warnings recorded in the baseline file
+    --write-baseline <FILE>
+                 (check) Record the current warnings in a baseline file
+    --explain    Print the explanation of an error code, e.g. E0001
+    --color=<WHEN>
+                 Color the diagnostics: auto (default), always or never
+    --error-format=<FORMAT>
+                 Print the diagnostics as human (default) or short, one line
+                 per label as `file:line:col: error[CODE]: message`
+    -A <LINT>    Allow a lint, `warnings` means all warnings
+    -W <LINT>    Warn about a lint
+    -D <LINT>    Deny a lint, e.g. `-D warnings` turns warnings into errors
+    -F <LINT>    Forbid a lint, like `-D` but can't be overridden
+";
+
+/// Command-line options of the compiler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub command: Command,
+
+    /// The source files to compile.
+    pub files: Vec<PathBuf>,
+
+    /// Lint levels set by `-A`, `-W`, `-D` and `-F`, in order.
+    pub lint_levels: Vec<(String, LintLevel)>,
+
+    /// Whether to color the diagnostics, set by `--color`.
+    pub color: ColorChoice,
+
+    /// How to print the diagnostics, set by `--error-format`.
+    pub error_format: ErrorFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Source snippets with the labels drawn under them.
+    Human,
+
+    /// One line per label, for editors and `grep`.
+    Short,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Prints the usage message.
+    Help,
+
+    /// Checks the source files and reports diagnostics.
+    Check {
+        /// Hides the warnings recorded in this baseline file.
+        baseline: Option<PathBuf>,
+
+        /// Records the warnings in this baseline file.
+        write_baseline: Option<PathBuf>,
+    },
+
+    /// Applies machine-applicable suggestions to the source files.
+    Fix {
+        /// Prints a unified diff instead of rewriting the files.
+        dry_run: bool,
+    },
+
+    /// Prints the explanation of an error code.
+    Explain {
+        code: String,
+    },
+}
+
+impl Options {
+    /// Returns the default options of a command without input files.
+    fn with_command(command: Command) -> Options {
+        Options {
+            command,
+            files: vec![],
+            lint_levels: vec![],
+            color: ColorChoice::Auto,
+            error_format: ErrorFormat::Human,
+        }
+    }
+
+    /// Parses the command-line arguments, without the program name. Returns
+    /// an error message if the arguments are invalid.
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut args = args.iter();
+
+        let mut command = match args.next().map(String::as_str) {
+            None | Some("help" | "-h" | "--help") => {
+                return Ok(Options::with_command(Command::Help));
+            }
+            Some("check") => Command::Check { baseline: None, write_baseline: None },
+            Some("fix") => Command::Fix { dry_run: false },
+            Some("--explain") => {
+                let code = args.next().ok_or("`--explain` requires an error code")?;
+                if let Some(extra) = args.next() {
+                    return Err(format!("unexpected argument `{}`", extra));
+                }
+                let command = Command::Explain { code: code.clone() };
+                return Ok(Options::with_command(command));
+            }
+            Some(other) => return Err(format!("unknown command `{}`", other)),
+        };
+
+        let mut files = vec![];
+        let mut lint_levels = vec![];
+        let mut color = ColorChoice::Auto;
+        let mut error_format = ErrorFormat::Human;
+        while let Some(arg) = args.next() {
+            if let Some(level) = lint_level_flag(arg) {
+                // Both `-D warnings` and `-Dwarnings` are accepted.
+                let lint = match &arg[2..] {
+                    "" => args.next().ok_or_else(|| format!("`{}` requires a lint name", arg))?,
+                    lint => lint,
+                };
+                lint_levels.push((lint.to_string(), level));
+                continue;
+            }
+
+            match arg.as_str() {
+                "--dry-run" => match command {
+                    Command::Fix { ref mut dry_run } => *dry_run = true,
+                    _ => return Err("`--dry-run` is only allowed in `kona fix`".to_string()),
+                },
+                "--baseline" | "--write-baseline" => {
+                    let file = args.next()
+                        .ok_or_else(|| format!("`{}` requires a file name", arg))?;
+                    match command {
+                        Command::Check { ref mut baseline, ref mut write_baseline } => {
+                            let target = if arg == "--baseline" { baseline } else { write_baseline };
+                            *target = Some(PathBuf::from(file));
+                        }
+                        _ => return Err(format!("`{}` is only allowed in `kona check`", arg)),
+                    }
+                }
+                "--color" => {
+                    let when = args.next().ok_or("`--color` requires a value")?;
+                    color = parse_color(when)?;
+                }
+                option if option.starts_with("--color=") => {
+                    color = parse_color(&option["--color=".len()..])?;
+                }
+                "--error-format" => {
+                    let format = args.next().ok_or("`--error-format` requires a value")?;
+                    error_format = parse_error_format(format)?;
+                }
+                option if option.starts_with("--error-format=") => {
+                    error_format = parse_error_format(&option["--error-format=".len()..])?;
+                }
+                "-h" | "--help" => command = Command::Help,
+                option if option.starts_with('-') => {
+                    return Err(format!("unknown option `{}`", option));
+                }
+                file => files.push(PathBuf::from(file)),
+            }
+        }
+
+        if files.is_empty() && command != Command::Help {
+            return Err("no input files".to_string());
+        }
+        Ok(Options { command, files, lint_levels, color, error_format })
+    }
+}
+
+fn parse_color(when: &str) -> Result<ColorChoice, String> {
+    match when {
+        "auto" => Ok(ColorChoice::Auto),
+        "always" => Ok(ColorChoice::Always),
+        "never" => Ok(ColorChoice::Never),
+        _ => Err(format!("`--color` must be `auto`, `always` or `never`, found `{}`", when)),
+    }
+}
+
+fn parse_error_format(format: &str) -> Result<ErrorFormat, String> {
+    match format {
+        "human" => Ok(ErrorFormat::Human),
+        "short" => Ok(ErrorFormat::Short),
+        _ => Err(format!("`--error-format` must be `human` or `short`, found `{}`", format)),
+    }
+}
+
+/// Returns the lint level set by a `-A`, `-W`, `-D` or `-F` argument.
+fn lint_level_flag(arg: &str) -> Option<LintLevel> {
+    match arg.get(..2)? {
+        "-A" => Some(LintLevel::Allow),
+        "-W" => Some(LintLevel::Warn),
+        "-D" => Some(LintLevel::Deny),
+        "-F" => Some(LintLevel::Forbid),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod options_tests {
+    use kona_diagnostic::diagnostic::{DiagnosticEngine, BufferEmitter, LintLevel};
+
+    use crate::lints;
+
+    use super::Options;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::parse(&args.split_whitespace().map(String::from).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_parse_lint_levels() {
+        let options = parse("check -A unused-binding -Wwarnings -D warnings -Finvisible-character a.sml")
+            .unwrap();
+        assert_eq!(options.lint_levels, [
+            ("unused-binding".to_string(), LintLevel::Allow),
+            ("warnings".to_string(), LintLevel::Warn),
+            ("warnings".to_string(), LintLevel::Deny),
+            ("invisible-character".to_string(), LintLevel::Forbid),
+        ]);
+        assert_eq!(options.files.len(), 1);
+
+        // Unknown lints are reported by the driver, not the parser.
+        let options = parse("check -D no-such-lint a.sml").unwrap();
+        assert_eq!(options.lint_levels, [("no-such-lint".to_string(), LintLevel::Deny)]);
+    }
+
+    #[test]
+    fn test_parse_lint_levels_missing_name() {
+        assert_eq!(parse("check a.sml -D").unwrap_err(), "`-D` requires a lint name");
+        assert_eq!(parse("check a.sml -F").unwrap_err(), "`-F` requires a lint name");
+    }
+
+    #[test]
+    fn test_forbidden_warnings_stay_forbidden() {
+        for args in [
+            "check -F warnings -W unused-binding a.sml",
+            "check -F warnings -A unused-binding a.sml",
+            "check -Fwarnings -Dwarnings -Aunused-binding a.sml",
+        ] {
+            let engine = DiagnosticEngine::with_emitter(Box::new(BufferEmitter::new(None)));
+            engine.register_lints(lints::LINTS);
+            for (name, level) in parse(args).unwrap().lint_levels {
+                engine.set_lint_level(&name, level);
+            }
+            assert_eq!(engine.lint_level(&lints::UNUSED_BINDING), LintLevel::Forbid, "{}", args);
+        }
+    }
+}
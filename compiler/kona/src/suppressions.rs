@@ -0,0 +1,193 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+//! Lint suppressions written as comments in the source code.
+//!
+//! SML has no attributes, so lints are suppressed with structured comments:
+//!
+//! - `(*@allow unused-binding*)` suppresses the lint in the declaration that
+//!   follows the comment.
+//! - `(*@!allow unused-binding*)` suppresses the lint in the enclosing
+//!   `struct ... end` or `sig ... end`, or in the whole file at top level.
+//!
+//! Several lints can be listed, separated by spaces or commas. Until the front
+//! end is there, declarations are found by a lightweight scan of the tokens:
+//! a declaration starts with a keyword like `val` or `fun`, and ends before
+//! the next declaration, `in`, `end` or `;` at the same nesting level.
+
+use std::rc::Rc;
+
+use kona_diagnostic::{
+    source::{SourceFile, Span},
+    diagnostic::Suppression,
+};
+
+use crate::tokens::{tokenize, Token, TokenKind};
+
+/// Keywords that start a declaration or a specification.
+const DECLARATION_KEYWORDS: &[&str] = &[
+    "val", "fun", "type", "datatype", "abstype", "exception", "local", "open",
+    "infix", "infixr", "nonfix", "structure", "signature", "functor",
+    "include", "eqtype",
+];
+
+/// Keywords that are closed by `end`.
+const BLOCK_KEYWORDS: &[&str] = &["struct", "sig", "let", "local", "abstype"];
+
+/// Finds the suppression comments in a source file.
+pub fn scan_suppressions(file: &Rc<SourceFile>) -> Vec<Suppression> {
+    let src = file.src();
+    let tokens = tokenize(&src);
+    let span = |start: usize, end: usize| {
+        Span::new(file.start_pos() + start, file.start_pos() + end)
+    };
+
+    // The nesting depth before each token, the blocks as the indices of their
+    // opening keyword and `end`, and the enclosing structure or signature of
+    // each comment.
+    let mut depths = Vec::with_capacity(tokens.len());
+    let mut blocks: Vec<(usize, Option<usize>)> = vec![];
+    let mut open_blocks: Vec<usize> = vec![];
+    let mut enclosing = vec![None; tokens.len()];
+    for (idx, token) in tokens.iter().enumerate() {
+        depths.push(open_blocks.len());
+        match token.kind {
+            TokenKind::Word if BLOCK_KEYWORDS.contains(&token.text(&src)) => {
+                open_blocks.push(blocks.len());
+                blocks.push((idx, None));
+            }
+            TokenKind::Word if token.text(&src) == "end" => {
+                if let Some(block) = open_blocks.pop() {
+                    blocks[block].1 = Some(idx);
+                }
+            }
+            TokenKind::Comment => {
+                enclosing[idx] = open_blocks
+                    .iter()
+                    .rev()
+                    .copied()
+                    .find(|&block| matches!(tokens[blocks[block].0].text(&src), "struct" | "sig"));
+            }
+            _ => {}
+        }
+    }
+
+    let mut suppressions = vec![];
+    for (idx, token) in tokens.iter().enumerate() {
+        if token.kind != TokenKind::Comment {
+            continue;
+        }
+        let (inner, lints) = match parse_allow(token.text(&src)) {
+            Some(allow) => allow,
+            None => continue,
+        };
+
+        let (start, end) = if inner {
+            match enclosing[idx] {
+                Some(block) => {
+                    let (open, close) = blocks[block];
+                    let end = close.map_or(src.len(), |close| tokens[close].end);
+                    (tokens[open].start, end)
+                }
+                None => (0, src.len()),
+            }
+        } else {
+            declaration_after(&tokens, &depths, &src, idx)
+                .unwrap_or((token.start, token.end))
+        };
+
+        for lint in lints {
+            suppressions.push(Suppression {
+                lint: lint.to_string(),
+                span: span(start, end),
+                source: span(token.start, token.end),
+            });
+        }
+    }
+    suppressions
+}
+
+/// Parses `(*@allow a, b*)` and `(*@!allow a, b*)`, returns whether it's the
+/// inner form and the lint names.
+fn parse_allow(comment: &str) -> Option<(bool, Vec<&str>)> {
+    let body = comment.strip_prefix("(*")?.strip_suffix("*)")?;
+    let (inner, rest) = match body.strip_prefix("@!allow") {
+        Some(rest) => (true, rest),
+        None => (false, body.strip_prefix("@allow")?),
+    };
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let lints = rest
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|lint| !lint.is_empty())
+        .collect::<Vec<_>>();
+    (!lints.is_empty()).then_some((inner, lints))
+}
+
+/// Returns the byte range of the declaration right after the comment at
+/// `idx`, or `None` if no declaration follows it.
+fn declaration_after(tokens: &[Token], depths: &[usize], src: &str, idx: usize) -> Option<(usize, usize)> {
+    let first = (idx + 1..tokens.len()).find(|&i| tokens[i].kind != TokenKind::Comment)?;
+    if !DECLARATION_KEYWORDS.contains(&tokens[first].text(src)) {
+        return None;
+    }
+
+    let depth = depths[first];
+    let mut last = first;
+    for (i, token) in tokens.iter().enumerate().skip(first + 1) {
+        if depths[i] < depth {
+            break;
+        }
+        if depths[i] == depth {
+            let text = token.text(src);
+            if DECLARATION_KEYWORDS.contains(&text) || matches!(text, "in" | "end" | ";") {
+                break;
+            }
+        }
+        if token.kind != TokenKind::Comment {
+            last = i;
+        }
+    }
+    Some((tokens[first].start, tokens[last].end))
+}
+
+#[cfg(test)]
+mod suppressions_tests {
+    use kona_diagnostic::source::SourceMap;
+
+    use super::scan_suppressions;
+
+    #[test]
+    fn test_scan_suppressions() {
+        let src = "\
+structure S = struct
+  (*@!allow shadowed-binding*)
+  (*@allow unused-binding, unused-type*)
+  fun f x =
+    let val y = \"(*@allow nothing*)\" in x end
+  val z = 1
+end
+(*@allow unused-binding*) (* not a declaration *) 42
+";
+        let source_map = SourceMap::new();
+        let file = source_map.load_test_file(None, src.to_string());
+        let suppressions = scan_suppressions(&file);
+
+        let sources = suppressions
+            .iter()
+            .map(|suppression| {
+                let text = source_map.lookup_source(suppression.span).unwrap();
+                (suppression.lint.as_str(), text)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(sources.len(), 4);
+        assert_eq!(sources[0].0, "shadowed-binding");
+        assert!(sources[0].1.starts_with("struct\n") && sources[0].1.ends_with("end"));
+        assert_eq!(sources[1], ("unused-binding",
+            "fun f x =\n    let val y = \"(*@allow nothing*)\" in x end".to_string()));
+        assert_eq!(sources[2].0, "unused-type");
+        assert_eq!(sources[3], ("unused-binding", "(*@allow unused-binding*)".to_string()));
+    }
+}
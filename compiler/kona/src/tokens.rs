@@ -0,0 +1,77 @@
+// Copyright (c) Kk Shinkai. All Rights Reserved. See LICENSE.txt in the project
+// root for license information.
+
+//! A lightweight tokenizer, used by the checks that only need to find comments
+//! and string literals until the front end is there.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// An alphanumeric identifier or keyword.
+    Word,
+    Comment,
+    String,
+    /// Any other character.
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token {
+    pub fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.start..self.end]
+    }
+}
+
+/// Splits the source into words, comments, strings and other characters,
+/// whitespace is skipped. Unterminated comments and strings run to the end.
+pub fn tokenize(src: &str) -> Vec<Token> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+
+    let mut tokens = vec![];
+    let mut chars = src.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let kind = if c.is_whitespace() {
+            continue;
+        } else if c == '(' && src[start + 1..].starts_with('*') {
+            chars.next();
+            let mut depth = 1;
+            while depth > 0 {
+                match chars.next() {
+                    Some((i, '(')) if src[i + 1..].starts_with('*') => {
+                        chars.next();
+                        depth += 1;
+                    }
+                    Some((i, '*')) if src[i + 1..].starts_with(')') => {
+                        chars.next();
+                        depth -= 1;
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            TokenKind::Comment
+        } else if c == '"' {
+            while let Some((_, c)) = chars.next() {
+                if c == '\\' {
+                    chars.next();
+                } else if c == '"' {
+                    break;
+                }
+            }
+            TokenKind::String
+        } else if is_word_char(c) {
+            while chars.next_if(|&(_, c)| is_word_char(c)).is_some() {}
+            TokenKind::Word
+        } else {
+            TokenKind::Other
+        };
+        let end = chars.peek().map_or(src.len(), |&(i, _)| i);
+        tokens.push(Token { kind, start, end });
+    }
+    tokens
+}